@@ -1,9 +1,15 @@
 '''This module provide a set of vectorized function to manipulate string, mostly mimic the main functionality of stringr package in R.
-As this package use pyarrow as a bridge to communicate with Rust, it ONLY work for any input that can convert to pyarray. And the resust is also a pyarry in most of cases.'''
+Arrays are passed to Rust through the Arrow PyCapsule interface, so any Arrow producer works as input. Other inputs,
+such as lists, are converted with pyarrow. The results are pyarrow arrays when pyarrow is installed.'''
 
-from pyarrow import Array, ListArray
-from typing import List, Union
+from __future__ import annotations
+from typing import TYPE_CHECKING, Callable, List, Tuple, Union
 from .utils import exporter, exporter2
+from . import _stringpy
+from ._stringpy import Pattern, fixed, regex
+
+if TYPE_CHECKING:
+    from pyarrow import Array, ListArray
 
 
 @exporter
@@ -52,13 +58,16 @@ def str_combine(*args, sep: str = None) -> List:
 
 
 @exporter(vectorize_arg=['pattern'])
-def str_count(array: Array, pattern: str = None) -> Array:
+def str_count(array: Array, pattern: Union[str, List[str]] = None, fixed: bool = False) -> Array:
     """Count the number of times a pattern occurs in each string
 
     Parameters
     ----------
     array : Array
-    pattern : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Examples
     --------
@@ -71,8 +80,8 @@ def str_count(array: Array, pattern: str = None) -> Array:
     """
 
 
-@exporter
-def str_replace(array: Array, pattern: str = None, replace: str = None) -> Array:
+@exporter(vectorize_arg=['pattern', 'replace'])
+def str_replace(array: Array, pattern: Union[str, List[str]] = None, replace: Union[str, List[str]] = None, fixed: bool = False) -> Array:
     """Replace a first matching pattern in string array. 
     Note that in this function, you can also use group in `replace`. For example, 
     in this case, I split a string in CamelCase.
@@ -86,8 +95,12 @@ def str_replace(array: Array, pattern: str = None, replace: str = None) -> Array
     Parameters
     ----------
     array : Array
-    pattern : str
-    replace : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
+    replace : Union[str, List[str]]
+        The replacement, or one replacement per string
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Examples
     --------
@@ -100,14 +113,17 @@ def str_replace(array: Array, pattern: str = None, replace: str = None) -> Array
     """
 
 
-@exporter
-def str_remove(array: Array, pattern: str = None) -> Array:
+@exporter(vectorize_arg=['pattern'])
+def str_remove(array: Array, pattern: Union[str, List[str]] = None, fixed: bool = False) -> Array:
     """Remove a first matching pattern in string array 
 
     Parameters
     ----------
     array : Array
-    pattern : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Examples
     --------
@@ -120,14 +136,17 @@ def str_remove(array: Array, pattern: str = None) -> Array:
     """
 
 
-@exporter
-def str_remove_all(array: Array, pattern: str = None) -> Array:
+@exporter(vectorize_arg=['pattern'])
+def str_remove_all(array: Array, pattern: Union[str, List[str]] = None, fixed: bool = False) -> Array:
     """Remove all matching pattern in string array 
 
     Parameters
     ----------
     array : Array
-    pattern : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Examples
     --------
@@ -140,15 +159,19 @@ def str_remove_all(array: Array, pattern: str = None) -> Array:
     """
 
 
-@exporter
-def str_replace_all(array: Array, pattern: str = None, replace: str = None) -> Array:
+@exporter(vectorize_arg=['pattern', 'replace'])
+def str_replace_all(array: Array, pattern: Union[str, List[str]] = None, replace: Union[str, List[str]] = None, fixed: bool = False) -> Array:
     """Replace all matching pattern in string array 
 
     Parameters
     ----------
     array : Array
-    pattern : str
-    replace : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
+    replace : Union[str, List[str]]
+        The replacement, or one replacement per string
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Examples
     --------
@@ -183,7 +206,9 @@ def str_squish(array: Array) -> Array:
 
 @exporter
 def str_remove_ascent(array: List) -> Array:
-    """Remove all accents from each string
+    """Remove all accents from each string. Letters are decomposed (NFD) and only their combining
+    diacritics are dropped, so letters of non-Latin scripts stay in their own script.
+    Use :func:`str_transliterate` to convert to ASCII.
 
     Parameters
     ----------
@@ -193,6 +218,27 @@ def str_remove_ascent(array: List) -> Array:
     --------
     >>> str_remove_ascent(['sài gòn', 'thời tiết', 'cảm lạnh']).to_pylist()
     ['sai gon', 'thoi tiet', 'cam lanh']
+    >>> str_remove_ascent(['Ёлка', 'Ελλάδα']).to_pylist()
+    ['Елка', 'Ελλαδα']
+
+    Returns
+    -------
+    Array
+    """
+
+
+@exporter
+def str_transliterate(array: Array) -> Array:
+    """Transliterate each string to ASCII, romanizing non-Latin scripts
+
+    Parameters
+    ----------
+    array : Array
+
+    Examples
+    --------
+    >>> str_transliterate(['Crème brûlée', 'Ёлка', '北京']).to_pylist()
+    ['Creme brulee', 'Iolka', 'Bei Jing ']
 
     Returns
     -------
@@ -201,13 +247,38 @@ def str_remove_ascent(array: List) -> Array:
 
 
 @exporter
-def str_detect(array: Array, pattern: str = None) -> Array:
+def str_normalize(array: Array, form: str = 'NFC') -> Array:
+    """Normalize each string to a Unicode normalization form, so that equivalent strings
+    have the same code points
+
+    Parameters
+    ----------
+    array : Array
+    form : str
+        One of 'NFC', 'NFD', 'NFKC', 'NFKD', by default 'NFC'
+
+    Examples
+    --------
+    >>> str_normalize(['e\u0301', 'ﬁ'], form='NFKC').to_pylist()
+    ['é', 'fi']
+
+    Returns
+    -------
+    Array
+    """
+
+
+@exporter(vectorize_arg=['pattern'])
+def str_detect(array: Array, pattern: Union[str, List[str]] = None, fixed: bool = False) -> Array:
     """Detect if each string match a pattern, return a boolean array
 
     Parameters
     ----------
     array : Array
-    pattern : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Examples
     --------
@@ -220,6 +291,82 @@ def str_detect(array: Array, pattern: str = None) -> Array:
     """
 
 
+@exporter
+def str_detect_any(array: Array, patterns: List[str] = None, fixed: bool = False) -> Array:
+    """Detect if each string matches any of several patterns, return a boolean array.
+    Each string is scanned once for all patterns, instead of once per pattern with `str_detect`.
+
+    Parameters
+    ----------
+    array : Array
+    patterns : List[str]
+        Regexes, or Patterns sharing the same options
+    fixed : bool
+        Match `patterns` as literal strings instead of regexes
+
+    Examples
+    --------
+    >>> str_detect_any(['abc', 'def', 'ghi'], patterns=[r'a', r'f$']).to_pylist()
+    [True, True, False]
+
+    Returns
+    -------
+    Array
+    """
+
+
+@exporter
+def str_which_patterns(array: Array, patterns: List[str] = None, fixed: bool = False) -> ListArray:
+    """Index of the patterns matching each string, return a list array.
+    Each string is scanned once for all patterns, null strings give null lists.
+
+    Parameters
+    ----------
+    array : Array
+    patterns : List[str]
+        Regexes, or Patterns sharing the same options
+    fixed : bool
+        Match `patterns` as literal strings instead of regexes
+
+    Examples
+    --------
+    >>> str_which_patterns(['abc', 'def', 'ghi'], patterns=[r'a', r'c', r'x']).to_pylist()
+    [[0, 1], [], []]
+
+    Returns
+    -------
+    ListArray
+    """
+
+
+@exporter
+def str_case_when(array: Array, cases: List[Tuple[str, str]] = None, default: str = None,
+                  fixed: bool = False) -> Array:
+    """Label each string with the first matching pattern, a vectorized ``case when``.
+    Each string is scanned once for all patterns, null strings stay null.
+
+    Parameters
+    ----------
+    array : Array
+    cases : List[Tuple[str, str]]
+        Ordered ``(pattern, label)`` pairs, the patterns must share the same options
+    default : str
+        Label of the strings matching no pattern, null by default
+    fixed : bool
+        Match the patterns as literal strings instead of regexes
+
+    Examples
+    --------
+    >>> str_case_when(['apple pie', 'banana', None, 'kiwi'],
+    ...               cases=[(r'apple|pear', 'pome'), (r'banana', 'berry')], default='other').to_pylist()
+    ['pome', 'berry', None, 'other']
+
+    Returns
+    -------
+    Array
+    """
+
+
 @exporter
 def str_trim(array: Array, side='both') -> Array:
     """Remove leading and trailing whitespace from each string
@@ -241,8 +388,9 @@ def str_trim(array: Array, side='both') -> Array:
 
 
 @exporter
-def str_trunc(array: Array, width: int = None, side='left', ellipsis='...') -> Array:
-    """Truncate each string to a given width, note that this function does NOT support non-ascii characters yet.
+def str_trunc(array: Array, width: int = None, side='left', ellipsis='...', unit: str = 'graphemes',
+              width_mode: str = 'length') -> Array:
+    """Truncate each string to a given width.
 
     Parameters
     ----------
@@ -252,6 +400,12 @@ def str_trunc(array: Array, width: int = None, side='left', ellipsis='...') -> A
         One of 'left', 'right', 'center'
     ellipsis : str
         Content of ellipsis that indicates content has been removed.
+    unit : str
+        Unit the string is measured in: 'bytes', 'codepoints' or 'graphemes' (user-perceived characters),
+        by default 'graphemes'
+    width_mode : str
+        'length' to count ``width`` in ``unit``, or 'display' to count it in columns of a monospace font,
+        where wide East Asian characters and emoji take two columns, by default 'length'
 
     Examples
     --------
@@ -264,16 +418,19 @@ def str_trunc(array: Array, width: int = None, side='left', ellipsis='...') -> A
     """
 
 
-@exporter
-def str_extract(array: Array, pattern: str = None, group: int = None) -> Array:
+@exporter(vectorize_arg=['pattern'])
+def str_extract(array: Array, pattern: Union[str, List[str]] = None, group: int = None, fixed: bool = False) -> Array:
     """Extract a first matching pattern in string array 
 
     Parameters
     ----------
     array : Array
-    pattern : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
     group : int
         Group number to extract, by default not use
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Examples
     --------
@@ -286,16 +443,19 @@ def str_extract(array: Array, pattern: str = None, group: int = None) -> Array:
     """
 
 
-@exporter
-def str_extract_all(array: Array, pattern: str = None, group: int = None) -> ListArray:
+@exporter(vectorize_arg=['pattern'])
+def str_extract_all(array: Array, pattern: Union[str, List[str]] = None, group: int = None, fixed: bool = False) -> ListArray:
     """Extract all matching pattern in string array, for each string input return list of matching output
 
     Parameters
     ----------
     array : Array
-    pattern : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
     group : int
         Group number to extract, by default not use
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Examples
     --------
@@ -308,14 +468,17 @@ def str_extract_all(array: Array, pattern: str = None, group: int = None) -> Lis
     """
 
 
-@exporter
-def str_split(array: Array, pattern: str = None) -> ListArray:
+@exporter(vectorize_arg=['pattern'])
+def str_split(array: Array, pattern: Union[str, List[str]] = None, fixed: bool = False) -> ListArray:
     """Split each string by a pattern, return a list[array], each array in the list is correspond to a string in input array
 
     Parameters
     ----------
     array : Array
-    pattern : str
+    pattern : Union[str, List[str]]
+        A regex, or one regex per string
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
 
     Returns
     -------
@@ -323,15 +486,16 @@ def str_split(array: Array, pattern: str = None) -> ListArray:
     """
 
 
-@exporter
-def str_starts(array: Array, pattern: str = None, negate: bool = False) -> Array:
+@exporter(vectorize_arg=['pattern'])
+def str_starts(array: Array, pattern: Union[str, List[str]] = None, negate: bool = False) -> Array:
     """Detect if each string starts with a pattern, return a boolean array
 
     Parameters
     ----------
     array : Array
-    pattern : str
-        Expect a literal string, not a regex, all regex special characters will be escaped
+    pattern : Union[str, List[str]]
+        A literal prefix, or one per string, all regex special characters are escaped.
+        A pattern built with ``regex()`` or ``fixed()`` is matched with its options at the start
     negate : bool
         Negate the result
     Examples
@@ -342,21 +506,25 @@ def str_starts(array: Array, pattern: str = None, negate: bool = False) -> Array
     >>> str_starts(['a.bc', 'adef', 'aghi'], pattern=r'a.').to_pylist()
     [True, False, False]
 
+    >>> str_starts(['Abc', 'xab'], pattern=regex('a', ignore_case=True)).to_pylist()
+    [True, False]
+
     Returns
     -------
     Array
     """
 
 
-@exporter
-def str_ends(array: Array, pattern: str = None, negate: bool = False) -> Array:
+@exporter(vectorize_arg=['pattern'])
+def str_ends(array: Array, pattern: Union[str, List[str]] = None, negate: bool = False) -> Array:
     """Detect if each string ends with a pattern, return a boolean array
 
     Parameters
     ----------
     array : Array
-    pattern : str
-        Expect a literal string, not a regex, all regex special characters will be escaped
+    pattern : Union[str, List[str]]
+        A literal suffix, or one per string, all regex special characters are escaped.
+        A pattern built with ``regex()`` or ``fixed()`` is matched with its options at the end
     negate : bool
         Negate the result
     Examples
@@ -367,6 +535,9 @@ def str_ends(array: Array, pattern: str = None, negate: bool = False) -> Array:
     >>> str_ends(['ab.c', 'defc', 'ghic'], pattern=r'.c').to_pylist()
     [True, False, False]
 
+    >>> str_ends(['ab1', 'ab'], pattern=regex(r'\d')).to_pylist()
+    [True, False]
+
     Returns
     -------
     Array
@@ -374,16 +545,18 @@ def str_ends(array: Array, pattern: str = None, negate: bool = False) -> Array:
 
 
 @exporter
-def str_subset(array: Array, pattern: str = None, negate: bool = False) -> Array:
+def str_subset(array: Array, pattern: str = None, negate: bool = False, fixed: bool = False) -> Array:
     """Subset (filter) array with a pattern, return string array
 
     Parameters
     ----------
     array : Array
     pattern : str
-        Expect a literal string, not a regex, all regex special characters will be escaped
     negate : bool
         Negate the result
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
+
     Examples
     --------
     >>> str_subset(['apple', 'banana', 'pear', 'pineapple'], pattern=r'^a').to_pylist()
@@ -419,14 +592,17 @@ def str_dup(array: Array, times: Union[int, List[int]] = 1) -> Array:
 
 
 @exporter()
-def str_length(array: Array) -> Array:
-    """Get length of each string in array.These are the individual elements (which are often, but not always letters)
-    For example length of "Hà Nội" will be 6
+def str_length(array: Array, unit: str = 'graphemes') -> Array:
+    """Get length of each string in array. By default these are the user-perceived characters, so
+    length of "Hà Nội" will be 6 whether its accents are precomposed or combining marks.
 
 
     Parameters
     ----------
     array : Array
+    unit : str
+        Unit the string is measured in: 'bytes', 'codepoints' or 'graphemes' (user-perceived characters),
+        by default 'graphemes'
 
     Examples
     --------
@@ -439,6 +615,26 @@ def str_length(array: Array) -> Array:
     """
 
 
+@exporter
+def str_width(array: Array) -> Array:
+    """Get the number of columns each string takes up in a monospace font, e.g. a terminal.
+    Wide East Asian characters and emoji take two columns, combining marks and control characters none.
+
+    Parameters
+    ----------
+    array : Array
+
+    Examples
+    --------
+    >>> str_width(['abc', '日本語', 'He\u0301llo', None]).to_pylist()
+    [3, 6, 5, None]
+
+    Returns
+    -------
+    Array
+    """
+
+
 @exporter
 def str_unique(array: Array) -> Array:
     """Get unique strings in array
@@ -459,17 +655,22 @@ def str_unique(array: Array) -> Array:
 
 
 @exporter
-def str_to_lower(array: Array) -> Array:
+def str_to_lower(array: Array, locale: str = None) -> Array:
     """Convert each string to lower case
 
     Parameters
     ----------
     array : Array
+    locale : str
+        Language whose case rules apply, e.g. 'tr' for the dotted and dotless i of Turkish,
+        by default None for the language independent rules
 
     Examples
     --------
     >>> str_to_lower(['ABC', 'Def', 'Ghi']).to_pylist()
     ['abc', 'def', 'ghi']
+    >>> str_to_lower(['ISTANBUL'], locale='tr').to_pylist()
+    ['ıstanbul']
 
     Returns
     -------
@@ -478,17 +679,22 @@ def str_to_lower(array: Array) -> Array:
 
 
 @exporter
-def str_to_upper(array: Array) -> Array:
+def str_to_upper(array: Array, locale: str = None) -> Array:
     """Convert each string to upper case
 
     Parameters
     ----------
     array : Array
+    locale : str
+        Language whose case rules apply, e.g. 'tr' for the dotted and dotless i of Turkish,
+        by default None for the language independent rules
 
     Examples
     --------
     >>> str_to_upper(['abc', 'Def', 'Ghi']).to_pylist()
     ['ABC', 'DEF', 'GHI']
+    >>> str_to_upper(['istanbul', 'straße'], locale='tr').to_pylist()
+    ['İSTANBUL', 'STRASSE']
 
     Returns
     -------
@@ -497,17 +703,28 @@ def str_to_upper(array: Array) -> Array:
 
 
 @exporter
-def str_to_title(array: Array) -> Array:
-    """Convert each string to title case
+def str_to_title(array: Array, locale: str = None, lower_rest: bool = False,
+                 keep_lower: List[str] = None) -> Array:
+    """Convert each string to title case: the first letter of each word is capitalized. Words are
+    split on Unicode word boundaries, so any whitespace or punctuation separates them.
 
     Parameters
     ----------
     array : Array
+    locale : str
+        Language whose case rules apply, e.g. 'tr' for the dotted and dotless i of Turkish,
+        by default None for the language independent rules
+    lower_rest : bool
+        Also lowercase the letters after the first one of each word
+    keep_lower : List[str]
+        Words kept in lower case unless they come first, e.g. ['of', 'and', 'the']
 
     Examples
     --------
     >>> str_to_title(['abc', 'def', 'ghi']).to_pylist()
     ['Abc', 'Def', 'Ghi']
+    >>> str_to_title(['the LORD of the rings'], lower_rest=True, keep_lower=['of', 'the']).to_pylist()
+    ['The Lord of the Rings']
 
     Returns
     -------
@@ -516,17 +733,80 @@ def str_to_title(array: Array) -> Array:
 
 
 @exporter
-def str_to_sentence(array: Array) -> Array:
-    """Convert each string to sentence case
+def str_to_sentence(array: Array, locale: str = None, lower_rest: bool = False) -> Array:
+    """Convert each string to sentence case: the first letter of each sentence is capitalized.
+    Sentences are split on Unicode sentence boundaries (UAX #29), which end with a terminator such as
+    '.', '!', '?' or '。' followed by whitespace, or with a line break. A period before a lowercase word
+    also ends a sentence, unless it follows an abbreviation with periods inside such as 'e.g.', or a
+    common one of the `locale` such as 'Dr.' in English.
 
     Parameters
     ----------
     array : Array
+    locale : str
+        Language whose case rules apply, e.g. 'tr' for the dotted and dotless i of Turkish,
+        by default None for the language independent rules
+    lower_rest : bool
+        Also lowercase the rest of each sentence
 
     Examples
     --------
     >>> str_to_sentence(['i need you here. right now!']).to_pylist()
     ['I need you here. Right now!']
+    >>> str_to_sentence(['WHERE ARE YOU?\tHERE'], lower_rest=True).to_pylist()
+    ['Where are you?\tHere']
+    >>> str_to_sentence(['dr. smith is here'], locale='en').to_pylist()
+    ['Dr. smith is here']
+
+    Returns
+    -------
+    Array
+    """
+
+
+@exporter
+def str_casefold(array: Array, locale: str = None) -> Array:
+    """Fold the case of each string, to compare strings ignoring case. Unlike :func:`str_to_lower`,
+    folding maps every case variant to the same string, e.g. 'ß' folds to 'ss'.
+
+    Parameters
+    ----------
+    array : Array
+    locale : str
+        Language whose case rules apply, e.g. 'tr' for the dotted and dotless i of Turkish,
+        by default None for the language independent rules
+
+    Examples
+    --------
+    >>> str_casefold(['Straße', 'STRASSE']).to_pylist()
+    ['strasse', 'strasse']
+
+    Returns
+    -------
+    Array
+    """
+
+
+@exporter(vectorize_arg=['other'])
+def str_equal(array: Array, other: Union[str, List[str]] = None, ignore_case: bool = False,
+              locale: str = None) -> Array:
+    """Test if each string equals another string
+
+    Parameters
+    ----------
+    array : Array
+    other : Union[str, List[str]]
+        A string, or one string per string of ``array``
+    ignore_case : bool
+        Compare the case folded strings, precomposed and combining accents also compare equal
+    locale : str
+        Language whose case rules apply, e.g. 'tr' for the dotted and dotless i of Turkish,
+        by default None for the language independent rules
+
+    Examples
+    --------
+    >>> str_equal(['STRASSE', 'Cafe\u0301', 'tea'], other=['straße', 'CAFÉ', 'coffee'], ignore_case=True).to_pylist()
+    [True, True, False]
 
     Returns
     -------
@@ -537,7 +817,9 @@ def str_to_sentence(array: Array) -> Array:
 @exporter(vectorize_arg=['width', 'side', 'pad'])
 def str_pad(array: Array, width: Union[int, List[int]] = None,
             side: Union[str, List[str]] = 'left',
-            pad: Union[str, List[str]] = ' ') -> Array:
+            pad: Union[str, List[str]] = ' ',
+            unit: str = 'graphemes',
+            width_mode: str = 'length') -> Array:
     """_summary_
 
     Parameters
@@ -550,6 +832,12 @@ def str_pad(array: Array, width: Union[int, List[int]] = None,
         _description_, by default 'left'
     pad : str, optional
         _description_, by default ' '
+    unit : str
+        Unit the string is measured in: 'bytes', 'codepoints' or 'graphemes' (user-perceived characters),
+        by default 'graphemes'
+    width_mode : str
+        'length' to count ``width`` in ``unit``, or 'display' to count it in columns of a monospace font,
+        where wide East Asian characters and emoji take two columns, by default 'length'
 
     Returns
     -------
@@ -580,7 +868,8 @@ def str_pad(array: Array, width: Union[int, List[int]] = None,
 
 
 @exporter(vectorize_arg=['start', 'end'])
-def str_sub(array: Array, start: Union[int, List[int]] = None, end: Union[int, List[int]] = None) -> Array:
+def str_sub(array: Array, start: Union[int, List[int]] = None, end: Union[int, List[int]] = None,
+            unit: str = 'graphemes') -> Array:
     """Get substring of each string in array by index, count from 0. Note that ``end`` is exclusive and
     must be larger thann ``start``. If provide negative index, it will be counted from the end of the string.
     In case ``start`` and ``end`` are out side of [0, length of string], it will be corced to the boundary.
@@ -592,6 +881,9 @@ def str_sub(array: Array, start: Union[int, List[int]] = None, end: Union[int, L
         Start position (inclusive)
     end : int
         End position (exclusive)
+    unit : str
+        Unit the string is measured in: 'bytes', 'codepoints' or 'graphemes' (user-perceived characters),
+        by default 'graphemes'
 
     Examples
     --------
@@ -626,12 +918,106 @@ def str_match(array: Array, pattern: Union[str, List] = None) -> Array:
 
 
 @exporter
-def str_locate(array: Array, pattern: str = None) -> Array:
+def str_locate(array: Array, pattern: str = None, fixed: bool = False) -> Array:
     """Locate the position of the first match of pattern in each string in array. Return -1 if not found.
 
     Parameters
     ----------
     array : Array
     pattern : str
-        Expect a literal string, not a regex, all regex special characters will be escaped
+    fixed : bool
+        Match `pattern` as a literal string instead of a regex, same as passing `fixed(pattern)`
     """
+
+
+def str_stream(reader, column: str, func: Callable, **kwargs):
+    """Apply a ``str_*`` function to one column of a stream of record batches.
+    Batches are read and transformed lazily, one at a time, so memory stays bounded by the batch size.
+
+    Parameters
+    ----------
+    reader :
+        Any Arrow stream: ``pa.RecordBatchReader``, ``pa.Table`` or any object implementing ``__arrow_c_stream__``
+    column : str
+        Name of the column to transform
+    func : Callable
+        One of the ``str_*`` functions
+    kwargs :
+        Arguments passed to ``func``
+
+    Examples
+    --------
+    >>> import pyarrow as pa
+    >>> table = pa.table({'x': ['abc', 'def'], 'y': [1, 2]})
+    >>> [b.to_pydict() for b in str_stream(table, 'x', str_to_upper)]
+    [{'x': ['ABC', 'DEF'], 'y': [1, 2]}]
+
+    Returns
+    -------
+    Iterator of ``pa.RecordBatch``, which can also be consumed as a stream with ``pa.RecordBatchReader.from_stream``
+    """
+    return _stringpy.str_stream(reader, column, func, **kwargs)
+
+
+def set_num_threads(num_threads: int = 0) -> None:
+    """Set the number of threads the kernels run on.
+    Kernels release the GIL while running, so other Python threads keep running meanwhile.
+
+    Parameters
+    ----------
+    num_threads : int
+        Number of threads, ``0`` restores the default: ``RAYON_NUM_THREADS`` if set, otherwise one per core
+
+    Examples
+    --------
+    >>> set_num_threads(2)
+    >>> get_num_threads()
+    2
+    >>> set_num_threads()
+    """
+    _stringpy.set_num_threads(num_threads)
+
+
+def get_num_threads() -> int:
+    """Get the number of threads the kernels run on.
+
+    Returns
+    -------
+    int
+    """
+    return _stringpy.get_num_threads()
+
+
+def set_cache_size(size: int = 1024) -> None:
+    """Set how many compiled patterns are kept in the process-wide cache.
+    Plain string patterns are looked up in it by source and options, so calls repeating the same
+    patterns skip compilation. The least recently used patterns are dropped first.
+
+    Parameters
+    ----------
+    size : int
+        Number of compiled patterns kept, ``0`` disables the cache
+
+    Examples
+    --------
+    >>> set_cache_size(2)
+    >>> cache_info()['maxsize']
+    2
+    >>> set_cache_size()
+    """
+    _stringpy.set_cache_size(size)
+
+
+def cache_info() -> dict:
+    """Statistics of the pattern cache: ``hits``, ``misses``, ``maxsize`` and ``currsize``.
+
+    Returns
+    -------
+    dict
+    """
+    return _stringpy.cache_info()
+
+
+def clear_cache() -> None:
+    """Drop all compiled patterns from the cache and reset its statistics."""
+    _stringpy.clear_cache()
@@ -0,0 +1,125 @@
+'''Register the ``str_*`` functions as ``pyarrow.compute`` scalar functions, named ``stringpy_*``:
+
+>>> import pyarrow as pa
+>>> import pyarrow.compute as pc
+>>> from stringpy.compute import register_udfs
+>>> register_udfs()
+>>> pc.call_function('stringpy_replace_all', [pa.array(['aa', 'ba']), pa.scalar('a'), pa.scalar('c')]).to_pylist()
+['cc', 'bc']
+
+Once registered they can be used in ``pc`` expressions, dataset filters and Acero plans.
+Every argument of the Python function becomes a positional argument of the compute function,
+arguments that can be vectorized (``width`` of ``str_pad``...) also accept arrays.
+Options in ``OPTIONS`` (``fixed``...) keep their default, so adding one never changes the arity
+of an already registered function.
+
+A registered function has a single signature, the functions taking ``pa.large_string()`` arrays
+are registered separately with the ``_large`` suffix, e.g. ``stringpy_replace_all_large``.'''
+
+import inspect
+import typing
+from typing import Callable, List
+import pyarrow as pa
+import pyarrow.compute as pc
+import stringpy
+from .utils import ELEMENTWISE
+
+# output type of each function, all others return strings
+OUT_TYPES = {
+    'str_count': pa.int32(),
+    'str_length': pa.int32(),
+    'str_width': pa.int32(),
+    'str_detect': pa.bool_(),
+    'str_equal': pa.bool_(),
+    'str_detect_any': pa.bool_(),
+    'str_which_patterns': pa.list_(pa.int32()),
+    'str_starts': pa.bool_(),
+    'str_ends': pa.bool_(),
+    'str_extract_all': pa.list_(pa.string()),
+    'str_split': pa.list_(pa.string()),
+    'str_match': pa.list_(pa.string()),
+    'str_locate': pa.list_(pa.int32()),
+}
+
+# functions taking arguments with no Arrow type, e.g. the (pattern, label) pairs of str_case_when
+NOT_UDF = ['str_case_when']
+
+# arguments bound to their default value instead of becoming an argument of the compute function
+OPTIONS = ['fixed', 'unit', 'width_mode', 'locale', 'lower_rest', 'keep_lower']
+
+# suffix of the functions taking large_string arrays
+LARGE_SUFFIX = '_large'
+
+
+def _arg_type(param: inspect.Parameter) -> pa.DataType:
+    annotation = param.annotation
+    if getattr(annotation, '__origin__', None) is typing.Union:
+        # vectorized arguments are annotated as Union[scalar, List[scalar]]
+        annotation = annotation.__args__[0]
+    if annotation is inspect.Parameter.empty:
+        annotation = type(param.default)
+    types = {str: pa.string(), int: pa.int64(), bool: pa.bool_()}
+    if getattr(annotation, '__origin__', None) is list:
+        # one list shared by all rows, e.g. the patterns of str_detect_any
+        return pa.list_(types[annotation.__args__[0]])
+    return types[annotation]
+
+
+def _large(data_type: pa.DataType) -> pa.DataType:
+    """Output type for a large_string input: strings and lists keep the 64-bit offsets of the input"""
+    if data_type == pa.string():
+        return pa.large_string()
+    if pa.types.is_list(data_type):
+        return pa.large_list(_large(data_type.value_type))
+    return data_type
+
+
+def _params(func: Callable) -> List[inspect.Parameter]:
+    # stringpy postpones the evaluation of its annotations, pyarrow types are only imported
+    # when type checking
+    hints = typing.get_type_hints(inspect.unwrap(func),
+                                  localns={'Array': pa.Array, 'ListArray': pa.ListArray})
+    params = list(inspect.signature(func).parameters.values())[1:]
+    return [p.replace(annotation=hints.get(p.name, p.annotation))
+            for p in params if p.name not in OPTIONS]
+
+
+def _udf(func: Callable, out_type: pa.DataType) -> Callable:
+    names = [p.name for p in _params(func)]
+
+    def udf(ctx, array, *args):
+        args = [x.as_py() if isinstance(x, pa.Scalar) else x.to_pylist() for x in args]
+        result = func(array, **dict(zip(names, args)))
+        # the output type must match exactly, including the field name of lists
+        return result if result.type == out_type else result.cast(out_type)
+    return udf
+
+
+def register_udfs(prefix: str = 'stringpy_'):
+    """Register every ``str_*`` function returning one value per row as a ``pyarrow.compute`` scalar function,
+    once for ``pa.string()`` arrays and once for ``pa.large_string()`` arrays with the ``_large`` suffix.
+    Functions already registered are skipped, so calling it twice is harmless.
+
+    Parameters
+    ----------
+    prefix : str
+        Replace the ``str_`` prefix of the function names
+    """
+    registered = set(pc.list_functions())
+    for name in ELEMENTWISE:
+        if name in NOT_UDF:
+            continue
+        func = getattr(stringpy, name)
+        args = {p.name: _arg_type(p) for p in _params(func)}
+        doc = {'summary': (func.__doc__ or name).strip().splitlines()[0],
+               'description': func.__doc__ or ''}
+        out_type = OUT_TYPES.get(name, pa.string())
+        variants = [('', pa.string(), out_type),
+                    (LARGE_SUFFIX, pa.large_string(), _large(out_type))]
+        for suffix, array_type, variant_out_type in variants:
+            udf_name = prefix + name[len('str_'):] + suffix
+            if udf_name in registered:
+                continue
+            in_types = {'array': array_type, **args}
+            pc.register_scalar_function(_udf(func, variant_out_type), udf_name, doc, in_types,
+                                        variant_out_type)
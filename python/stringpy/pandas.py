@@ -0,0 +1,48 @@
+'''Expose the ``str_*`` functions on pandas Series, under the ``stringpy`` accessor.
+Importing this module registers the accessor, the ``str_`` prefix is dropped from the names:
+
+>>> import pandas as pd
+>>> import stringpy.pandas
+>>> s = pd.Series(['abc', 'def', None], index=[2, 4, 6], name='x')
+>>> s.stringpy.to_upper()
+2     ABC
+4     DEF
+6    None
+Name: x, dtype: object
+
+Series of ``pd.ArrowDtype`` are passed to Rust without copy and come back as ``pd.ArrowDtype``,
+object Series come back as object Series. Both keep their index and name.'''
+
+from typing import Callable
+import pandas as pd
+import pyarrow as pa
+import stringpy
+from .utils import ELEMENTWISE, NOT_ELEMENTWISE
+
+
+def _method(func: Callable, is_elementwise: bool):
+    def method(self, **kwargs) -> pd.Series:
+        s = self._series
+        result = func(pa.array(s, from_pandas=True), **kwargs)
+        # only functions keeping one value per row keep the index
+        index = s.index if is_elementwise else None
+        if isinstance(s.dtype, pd.ArrowDtype):
+            return pd.Series(result, index=index, name=s.name, dtype=pd.ArrowDtype(result.type))
+        return pd.Series(result.to_numpy(zero_copy_only=False), index=index, name=s.name)
+    method.__name__ = func.__name__[len('str_'):]
+    method.__doc__ = func.__doc__
+    return method
+
+
+@pd.api.extensions.register_series_accessor('stringpy')
+class StringpyAccessor:
+    def __init__(self, series: pd.Series):
+        if not (series.dtype == object or series.dtype == pd.ArrowDtype(pa.string())
+                or series.dtype == pd.ArrowDtype(pa.large_string())):
+            raise AttributeError('Can only use .stringpy accessor with string values')
+        self._series = series
+
+
+for name in ELEMENTWISE + NOT_ELEMENTWISE:
+    method = _method(getattr(stringpy, name), name in ELEMENTWISE)
+    setattr(StringpyAccessor, method.__name__, method)
@@ -0,0 +1,124 @@
+'''Expose the ``str_*`` functions on polars expressions, under the ``stringpy`` namespace.
+Importing this module registers the namespace, the ``str_`` prefix is dropped from the names:
+
+>>> import polars as pl
+>>> import stringpy.polars
+>>> df = pl.DataFrame({'x': ['abc', 'def', None], 'p': ['a', 'e', 'i']})
+>>> df.select(pl.col('x').stringpy.detect(pattern='a'))['x'].to_list()
+[True, False, None]
+>>> df.select(pl.col('x').stringpy.detect(pattern=pl.col('p')))['x'].to_list()
+[True, True, None]
+
+The functions are a native polars expression plugin, built from the same Rust kernels in the
+``stringpy-polars`` package: polars calls them without the GIL and without a pyarrow conversion,
+and the lazy engine can run them in parallel. Per-row arguments, e.g. one pattern per string, are
+polars expressions such as ``pl.col('p')`` instead of lists.'''
+
+import inspect
+from typing import Any, List
+import polars as pl
+from polars.plugins import register_plugin_function
+from stringpy_polars import PLUGIN_PATH
+import stringpy
+from ._stringpy import Pattern
+from .utils import ELEMENTWISE, NOT_ELEMENTWISE, sync_kw
+
+# arguments given per row, they are passed to the plugin as columns after the strings, in this order
+PER_ROW = {
+    'str_count': ['pattern'],
+    'str_replace': ['pattern', 'replace'],
+    'str_replace_all': ['pattern', 'replace'],
+    'str_remove': ['pattern'],
+    'str_remove_all': ['pattern'],
+    'str_detect': ['pattern'],
+    'str_extract': ['pattern'],
+    'str_extract_all': ['pattern'],
+    'str_split': ['pattern'],
+    'str_starts': ['pattern'],
+    'str_ends': ['pattern'],
+    'str_dup': ['times'],
+    'str_equal': ['other'],
+    'str_pad': ['width', 'side', 'pad'],
+    'str_sub': ['start', 'end'],
+}
+
+# functions matching a plain string pattern literally
+LITERAL = ['str_starts', 'str_ends']
+
+# options of a pattern, in the order of the attributes of `Pattern`
+FLAGS = ['fixed', 'ignore_case', 'multiline', 'dotall', 'comments', 'unicode']
+
+# options of a plain string or a column of patterns, besides `fixed`
+DEFAULT_FLAGS = (False, False, False, False, True)
+
+
+def _flags(pattern: Any, fixed: bool) -> tuple:
+    if isinstance(pattern, Pattern):
+        return tuple(getattr(pattern, flag) for flag in FLAGS)
+    return (fixed,) + DEFAULT_FLAGS
+
+
+def _options(patterns: List[Any], fixed: bool) -> dict:
+    options = {_flags(p, fixed) for p in patterns} or {_flags(None, fixed)}
+    if len(options) > 1:
+        raise ValueError('Patterns of one call must share the same options')
+    return dict(zip(FLAGS, options.pop()))
+
+
+def _source(pattern: Any) -> Any:
+    return pattern.source if isinstance(pattern, Pattern) else pattern
+
+
+def _column(name: str, value: Any) -> pl.Expr:
+    if isinstance(value, pl.Expr):
+        return value
+    if isinstance(value, (list, tuple, pl.Series)):
+        raise TypeError(f'{name} must be a single value, or a polars expression giving one value per '
+                        f'row such as pl.col({name!r})')
+    return pl.lit(_source(value))
+
+
+def _method(name: str):
+    func = getattr(stringpy, name)
+    defaults = {k: v.default for k, v in inspect.signature(func).parameters.items()
+                if v.default is not inspect.Parameter.empty}
+    per_row = PER_ROW.get(name, [])
+
+    def method(self, **kwargs) -> pl.Expr:
+        kw = dict(defaults)
+        sync_kw(kwargs, kw)
+        fixed = kw.pop('fixed', name in LITERAL)
+        if 'cases' in kw:
+            cases = kw.pop('cases')
+            kw['patterns'] = [pattern for pattern, _ in cases]
+            kw['labels'] = [label for _, label in cases]
+        if 'patterns' in kw:
+            kw['options'] = _options(kw['patterns'], fixed)
+            kw['patterns'] = [_source(p) for p in kw['patterns']]
+        elif 'pattern' in kw:
+            kw['options'] = _options([kw['pattern']], fixed)
+            if 'pattern' not in per_row:
+                kw['pattern'] = _source(kw['pattern'])
+        args = [self._expr] + [_column(arg, kw.pop(arg)) for arg in per_row]
+        return register_plugin_function(
+            plugin_path=PLUGIN_PATH,
+            function_name=name,
+            args=args,
+            kwargs=kw or None,
+            is_elementwise=name in ELEMENTWISE,
+            changes_length=name in NOT_ELEMENTWISE,
+        )
+    method.__name__ = name[len('str_'):]
+    method.__doc__ = func.__doc__
+    return method
+
+
+@pl.api.register_expr_namespace('stringpy')
+class StringpyNamespace:
+    def __init__(self, expr: pl.Expr):
+        self._expr = expr
+
+
+for name in ELEMENTWISE + NOT_ELEMENTWISE:
+    method = _method(name)
+    setattr(StringpyNamespace, method.__name__, method)
@@ -1,12 +1,12 @@
 
 import inspect
+import sys
 from functools import wraps
 from typing import Callable, List, TypeVar, Any
-import pyarrow as pa
 from stringpy import _stringpy
 
 
-def check_same_length(lists: List[pa.Array]):
+def check_same_length(lists: List[Any]):
     it = iter(lists)
     the_len = len(next(it))
     if not all(len(i) == the_len for i in it):
@@ -22,7 +22,40 @@ def sync_kw(kwargs, kw_with_defaults):
 
 
 def is_scala(x: any):
-    return isinstance(x, (int, float, str, bool))
+    return isinstance(x, (int, float, str, bool, _stringpy.Pattern))
+
+
+def is_arrow(x: any):
+    """Whether `x` can be passed to Rust as is, through pyarrow or the Arrow PyCapsule interface"""
+    if hasattr(x, '__arrow_c_array__') or hasattr(x, '__arrow_c_stream__'):
+        return True
+    # a pyarrow object can only exist once pyarrow is imported, don't import it just to check
+    pa = sys.modules.get('pyarrow')
+    return pa is not None and isinstance(x, (pa.Array, pa.ChunkedArray))
+
+
+def to_arrow(x: any):
+    """Convert `x` to an arrow array with pyarrow, unless it can be passed to Rust as is"""
+    if is_arrow(x):
+        return x
+    try:
+        import pyarrow as pa
+    except ImportError as e:
+        raise ImportError(
+            f'pyarrow is required to convert {type(x).__name__} to an arrow array, install it or '
+            'pass an object implementing the Arrow PyCapsule interface') from e
+    return pa.array(x)
+
+# functions returning one value per row, other functions change the length of the column
+ELEMENTWISE = [
+    'str_count', 'str_replace', 'str_remove', 'str_remove_all', 'str_replace_all', 'str_squish',
+    'str_remove_ascent', 'str_transliterate', 'str_normalize', 'str_detect', 'str_detect_any',
+    'str_which_patterns', 'str_case_when', 'str_trim', 'str_trunc', 'str_extract',
+    'str_extract_all', 'str_split', 'str_starts', 'str_ends', 'str_dup', 'str_length',
+    'str_width', 'str_to_lower', 'str_to_upper', 'str_to_title', 'str_to_sentence',
+    'str_casefold', 'str_equal', 'str_pad', 'str_sub', 'str_match', 'str_locate',
+]
+NOT_ELEMENTWISE = ['str_subset', 'str_unique']
 
 _TFunc = TypeVar("_TFunc", bound=Callable[..., Any])
 
@@ -36,25 +69,18 @@ def exporter(_func=None, vectorize_arg: List = None):
             kw_with_defaults = {k: v.default for k, v in dict(inspect.signature(
                 func).parameters).items() if v.default is not inspect._empty}
 
-            # FIXME need to check length =1 , length = array, corece to array length if = 1
-            array = pa.array(array) if not isinstance(
-                array, pa.Array) else array
+            array = to_arrow(array)
             sync_kw(kwargs, kw_with_defaults)
 
             if vectorize_arg is not None:
+                # the length of a vectorized argument is checked in Rust once the array is imported,
+                # PyCapsule producers don't have to implement `__len__`
                 for i in vectorize_arg:
-
                     if is_scala(kw_with_defaults[i]):
                         kw_with_defaults[i] = [kw_with_defaults[i]]
                     elif type(kw_with_defaults[i]) is not list:
                         kw_with_defaults[i] = list(kw_with_defaults[i])
 
-                    item_len = len(kw_with_defaults[i])
-
-                    if not (item_len == 1) | (item_len == len(array)):
-                        raise ValueError(
-                            f"Length of `{i}` must be equal to 1 or to length of array")
-
             return getattr(_stringpy, rust_func)(array, **kw_with_defaults)
         return inner
     if _func is None:
@@ -77,8 +103,7 @@ def exporter2(func: Callable):
         check_same_length(args)
 
         args = list(args)
-        args = [pa.array(a) if not isinstance(
-            a, pa.Array) else a for a in args]
+        args = [to_arrow(a) for a in args]
         args = tuple(args)
         sync_kw(kwargs, kw_with_defaults)
 
@@ -5,10 +5,17 @@
 use std::error;
 use std::fmt;
 
-use arrow2::{array::Array, datatypes::Field, error::Error, ffi};
-use pyo3::exceptions::PyOSError;
+use arrow2::{
+    array::{new_empty_array, Array},
+    datatypes::{DataType, Field},
+    error::Error,
+    ffi,
+};
+use pyo3::exceptions::{PyImportError, PyOSError, PyValueError};
 use pyo3::ffi::Py_uintptr_t;
 use pyo3::prelude::*;
+use pyo3::types::PyCapsule;
+use std::ffi::CString;
 
 /// an error that bridges Error with a Python error
 #[derive(Debug)]
@@ -47,7 +54,59 @@ impl From<PyO3Error> for PyErr {
     }
 }
 
+/// A value owned by a capsule. The FFI structs hold raw pointers, but the producer
+/// guarantees they can be released from any thread.
+#[repr(transparent)]
+struct CapsuleValue<T>(T);
+
+unsafe impl<T> Send for CapsuleValue<T> {}
+
+pub fn new_capsule<T: 'static>(py: Python, value: T, name: &str) -> PyResult<PyObject> {
+    let name = CString::new(name).unwrap();
+    // dropping the value calls its `release` callback, unless a consumer already moved it out
+    let capsule = PyCapsule::new_with_destructor(py, CapsuleValue(value), Some(name), |_, _| {})?;
+    Ok(capsule.to_object(py))
+}
+
+/// The pointer held by a capsule of the Arrow PyCapsule interface, checking the capsule is the
+/// struct `name` expects before it is cast
+fn capsule_pointer<T>(capsule: &PyCapsule, name: &str) -> PyResult<*mut T> {
+    let found = capsule.name()?.map(|found| found.to_string_lossy());
+    if found.as_deref() != Some(name) {
+        return Err(PyValueError::new_err(format!(
+            "Expected a capsule named '{}', got '{}'",
+            name,
+            found.unwrap_or_default()
+        )));
+    }
+    let pointer = capsule.pointer() as *mut T;
+    if pointer.is_null() {
+        return Err(PyValueError::new_err(format!(
+            "The capsule '{}' holds a null pointer",
+            name
+        )));
+    }
+    Ok(pointer)
+}
+
+/// Import an array from any object implementing the Arrow PyCapsule interface
+/// (`__arrow_c_array__`), e.g. pyarrow, polars, nanoarrow or duckdb arrays.
+/// Falls back to pyarrow's private `_export_to_c` for pyarrow versions without the interface.
 pub fn to_rust_array(ob: PyObject, py: Python) -> PyResult<Box<dyn Array>> {
+    if ob.as_ref(py).hasattr("__arrow_c_array__")? {
+        let capsules = ob.call_method0(py, "__arrow_c_array__")?.into_ref(py);
+        let (schema, array): (&PyCapsule, &PyCapsule) = capsules.extract()?;
+
+        let schema = capsule_pointer::<ffi::ArrowSchema>(schema, "arrow_schema")?;
+        let array = capsule_pointer::<ffi::ArrowArray>(array, "arrow_array")?;
+        let field = unsafe { ffi::import_field_from_c(&*schema).map_err(PyO3Error::from)? };
+        // move the array out of its capsule, which then has nothing left to release
+        let array = unsafe { std::ptr::replace(array, ffi::ArrowArray::empty()) };
+        let array =
+            unsafe { ffi::import_array_from_c(array, field.data_type).map_err(PyO3Error::from)? };
+        return Ok(array);
+    }
+
     // prepare a pointer to receive the Array struct
     let array = Box::new(ffi::ArrowArray::empty());
     let schema = Box::new(ffi::ArrowSchema::empty());
@@ -70,8 +129,166 @@ pub fn to_rust_array(ob: PyObject, py: Python) -> PyResult<Box<dyn Array>> {
     Ok(array)
 }
 
-#[allow(dead_code)]
+/// Whether `ob` is chunked (e.g. a `pa.ChunkedArray`, a table column or a polars Series)
+/// rather than a single array
+pub fn is_chunked(ob: &PyObject, py: Python) -> PyResult<bool> {
+    let ob = ob.as_ref(py);
+    if ob.hasattr("__arrow_c_array__")? {
+        return Ok(false);
+    }
+    Ok(ob.hasattr("__arrow_c_stream__")? || ob.hasattr("chunks")?)
+}
+
+/// Import a stream from any object implementing `__arrow_c_stream__`, e.g. a `pa.RecordBatchReader`,
+/// a `pa.Table` or a `pa.ChunkedArray`. Falls back to `_export_to_c` for older pyarrow readers.
+pub fn to_rust_stream(ob: PyObject, py: Python) -> PyResult<ffi::ArrowArrayStreamReader> {
+    let stream = if ob.as_ref(py).hasattr("__arrow_c_stream__")? {
+        let capsule = ob.call_method0(py, "__arrow_c_stream__")?.into_ref(py);
+        let capsule: &PyCapsule = capsule.downcast()?;
+
+        let stream = capsule_pointer::<ffi::ArrowArrayStream>(capsule, "arrow_array_stream")?;
+        // move the stream out of its capsule, which then has nothing left to release
+        unsafe { std::ptr::replace(stream, ffi::ArrowArrayStream::empty()) }
+    } else {
+        let stream = Box::new(ffi::ArrowArrayStream::empty());
+        let stream_ptr = &*stream as *const ffi::ArrowArrayStream;
+        ob.call_method1(py, "_export_to_c", (stream_ptr as Py_uintptr_t,))?;
+        *stream
+    };
+    let reader =
+        unsafe { ffi::ArrowArrayStreamReader::try_new(Box::new(stream)).map_err(PyO3Error::from)? };
+    Ok(reader)
+}
+
+/// Import every chunk of a chunked object together with its data type
+pub fn to_rust_chunks(ob: PyObject, py: Python) -> PyResult<(DataType, Vec<Box<dyn Array>>)> {
+    if ob.as_ref(py).hasattr("__arrow_c_stream__")? {
+        let mut reader = to_rust_stream(ob, py)?;
+        let data_type = reader.field().data_type.clone();
+        let mut chunks = vec![];
+        while let Some(chunk) = unsafe { reader.next() } {
+            chunks.push(chunk.map_err(PyO3Error::from)?);
+        }
+        return Ok((data_type, chunks));
+    }
+
+    let data_type = to_rust_field(ob.getattr(py, "type")?, py)?.data_type;
+    let chunks: Vec<PyObject> = ob.getattr(py, "chunks")?.extract(py)?;
+    let chunks = chunks
+        .into_iter()
+        .map(|chunk| to_rust_array(chunk, py))
+        .collect::<PyResult<_>>()?;
+    Ok((data_type, chunks))
+}
+
+/// Export arrays as the chunks of a `pa.ChunkedArray` of type `data_type`.
+/// Without pyarrow installed, an [`ArrowChunkedArray`] is returned instead.
+pub fn to_py_chunked_array(
+    chunks: Vec<Box<dyn Array>>,
+    data_type: DataType,
+    py: Python,
+) -> PyResult<PyObject> {
+    let pa = match py.import("pyarrow") {
+        Ok(pa) => pa,
+        Err(err) if err.is_instance_of::<PyImportError>(py) => {
+            let field = Field::new("", data_type, true);
+            return Ok(ArrowChunkedArray { field, chunks }.into_py(py));
+        }
+        Err(err) => return Err(err),
+    };
+
+    // the type of an empty array, so that it goes through the same export path as the chunks
+    let data_type = to_py_array(new_empty_array(data_type), py)?.getattr(py, "type")?;
+    let chunks = chunks
+        .into_iter()
+        .map(|chunk| to_py_array(chunk, py))
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let array = pa.call_method1("chunked_array", (chunks, data_type))?;
+
+    Ok(array.to_object(py))
+}
+
+/// An arrow array exported through the Arrow PyCapsule interface, returned as is when pyarrow
+/// is not installed. Any library supporting the interface can import it, e.g. `pyarrow.array(x)`
+/// or `polars.Series(x)`.
+#[pyclass(module = "stringpy._stringpy")]
+pub struct ArrowArray {
+    array: Box<dyn Array>,
+}
+
+#[pymethods]
+impl ArrowArray {
+    #[pyo3(signature = (requested_schema=None))]
+    fn __arrow_c_array__(
+        &self,
+        py: Python,
+        requested_schema: Option<PyObject>,
+    ) -> PyResult<(PyObject, PyObject)> {
+        let _ = requested_schema;
+        let field = Field::new("", self.array.data_type().clone(), true);
+        let schema = new_capsule(py, ffi::export_field_to_c(&field), "arrow_schema")?;
+        let array = new_capsule(
+            py,
+            ffi::export_array_to_c(self.array.clone()),
+            "arrow_array",
+        )?;
+        Ok((schema, array))
+    }
+
+    fn __len__(&self) -> usize {
+        self.array.len()
+    }
+}
+
+/// A chunked arrow array returned when pyarrow is not installed, exported through
+/// `__arrow_c_stream__` one chunk at a time.
+#[pyclass(module = "stringpy._stringpy")]
+pub struct ArrowChunkedArray {
+    field: Field,
+    chunks: Vec<Box<dyn Array>>,
+}
+
+#[pymethods]
+impl ArrowChunkedArray {
+    #[pyo3(signature = (requested_schema=None))]
+    fn __arrow_c_stream__(
+        &self,
+        py: Python,
+        requested_schema: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let _ = requested_schema;
+        let chunks = self.chunks.clone().into_iter().map(Ok);
+        let stream = ffi::export_iterator(Box::new(chunks), self.field.clone());
+        new_capsule(py, stream, "arrow_array_stream")
+    }
+
+    fn __len__(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.len()).sum()
+    }
+}
+
+/// Whether pyarrow imports objects implementing the Arrow PyCapsule interface (pyarrow >= 14)
+fn imports_capsules(pa: &PyModule) -> PyResult<bool> {
+    pa.getattr("Array")?.hasattr("__arrow_c_array__")
+}
+
+/// Export an array as a `pa.Array` through the Arrow PyCapsule interface, or through pyarrow's
+/// private `_import_from_c` for pyarrow versions without it.
+/// Without pyarrow installed, an [`ArrowArray`] is returned instead.
 pub fn to_py_array(array: Box<dyn Array>, py: Python) -> PyResult<PyObject> {
+    let pa = match py.import("pyarrow") {
+        Ok(pa) => pa,
+        Err(err) if err.is_instance_of::<PyImportError>(py) => {
+            return Ok(ArrowArray { array }.into_py(py));
+        }
+        Err(err) => return Err(err),
+    };
+    if imports_capsules(pa)? {
+        let array = pa.call_method1("array", (ArrowArray { array }.into_py(py),))?;
+        return Ok(array.to_object(py));
+    }
+
     let schema = Box::new(ffi::export_field_to_c(&Field::new(
         "",
         array.data_type().clone(),
@@ -82,8 +299,6 @@ pub fn to_py_array(array: Box<dyn Array>, py: Python) -> PyResult<PyObject> {
     let schema_ptr: *const arrow2::ffi::ArrowSchema = &*schema;
     let array_ptr: *const arrow2::ffi::ArrowArray = &*array;
 
-    let pa = py.import("pyarrow")?;
-
     let array = pa.getattr("Array")?.call_method1(
         "_import_from_c",
         (array_ptr as Py_uintptr_t, schema_ptr as Py_uintptr_t),
@@ -92,8 +307,25 @@ pub fn to_py_array(array: Box<dyn Array>, py: Python) -> PyResult<PyObject> {
     Ok(array.to_object(py))
 }
 
-#[allow(dead_code)]
-fn to_rust_field(ob: PyObject, py: Python) -> PyResult<Field> {
+/// Export a struct array as a `pa.RecordBatch`. Without pyarrow installed, an [`ArrowArray`] is returned instead.
+pub fn to_py_record_batch(array: Box<dyn Array>, py: Python) -> PyResult<PyObject> {
+    let pa = match py.import("pyarrow") {
+        Ok(pa) => pa,
+        Err(err) if err.is_instance_of::<PyImportError>(py) => {
+            return Ok(ArrowArray { array }.into_py(py));
+        }
+        Err(err) => return Err(err),
+    };
+
+    let array = to_py_array(array, py)?;
+    let batch = pa
+        .getattr("RecordBatch")?
+        .call_method1("from_struct_array", (array,))?;
+
+    Ok(batch.to_object(py))
+}
+
+pub fn to_rust_field(ob: PyObject, py: Python) -> PyResult<Field> {
     // prepare a pointer to receive the Array struct
     let schema = Box::new(ffi::ArrowSchema::empty());
 
@@ -107,17 +339,3 @@ fn to_rust_field(ob: PyObject, py: Python) -> PyResult<Field> {
 
     Ok(field)
 }
-
-#[allow(dead_code)]
-fn to_py_field(field: &Field, py: Python) -> PyResult<PyObject> {
-    let schema = Box::new(ffi::export_field_to_c(field));
-    let schema_ptr: *const arrow2::ffi::ArrowSchema = &*schema;
-
-    let pa = py.import("pyarrow")?;
-
-    let array = pa
-        .getattr("Field")?
-        .call_method1("_import_from_c", (schema_ptr as Py_uintptr_t,))?;
-
-    Ok(array.to_object(py))
-}
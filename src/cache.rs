@@ -0,0 +1,146 @@
+//! Process-wide LRU cache of the patterns compiled from plain strings, so that calls
+//! repeating the same patterns over many batches skip compilation.
+
+use crate::error::StringpyErr;
+use lru::LruCache;
+use pyo3::prelude::*;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use stringpy_core::pattern::{Matcher, Options};
+
+const DEFAULT_SIZE: usize = 1024;
+
+/// A cache key, the entries own their source but lookups borrow it so they don't allocate
+trait Key {
+    fn parts(&self) -> (&str, Options);
+}
+
+impl Key for (String, Options) {
+    fn parts(&self) -> (&str, Options) {
+        (&self.0, self.1)
+    }
+}
+
+impl Key for (&str, Options) {
+    fn parts(&self) -> (&str, Options) {
+        (self.0, self.1)
+    }
+}
+
+impl Hash for dyn Key + '_ {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // same hash as the owned tuple, `String` hashes as `str`
+        self.parts().hash(state)
+    }
+}
+
+impl PartialEq for dyn Key + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.parts() == other.parts()
+    }
+}
+
+impl Eq for dyn Key + '_ {}
+
+impl<'a> Borrow<dyn Key + 'a> for (String, Options) {
+    fn borrow(&self) -> &(dyn Key + 'a) {
+        self
+    }
+}
+
+struct Cache {
+    // unbounded, `size` is enforced on insert so that 0 can disable the cache
+    entries: LruCache<(String, Options), Arc<Matcher>>,
+    size: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl Cache {
+    fn get(&mut self, source: &str, options: Options) -> Option<Arc<Matcher>> {
+        let key: &dyn Key = &(source, options);
+        match self.entries.get(key) {
+            Some(matcher) => {
+                self.hits += 1;
+                Some(matcher.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, source: &str, options: Options, matcher: Arc<Matcher>) {
+        if self.size == 0 {
+            return;
+        }
+        self.entries.put((source.to_string(), options), matcher);
+        self.evict(self.size);
+    }
+
+    /// Drop the least recently used entries until at most `size` are left
+    fn evict(&mut self, size: usize) {
+        while self.entries.len() > size {
+            self.entries.pop_lru();
+        }
+    }
+}
+
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+fn with_cache<T>(f: impl FnOnce(&mut Cache) -> T) -> T {
+    let mut cache = CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(|| Cache {
+        entries: LruCache::unbounded(),
+        size: DEFAULT_SIZE,
+        hits: 0,
+        misses: 0,
+    });
+    f(cache)
+}
+
+/// Compile `source`, or reuse the matcher compiled by a previous call
+pub fn compile(source: &str, options: Options) -> Result<Arc<Matcher>, StringpyErr> {
+    if let Some(matcher) = with_cache(|cache| cache.get(source, options)) {
+        return Ok(matcher);
+    }
+    // compile without holding the lock
+    let matcher = Arc::new(Matcher::new(source, options)?);
+    with_cache(|cache| cache.insert(source, options, matcher.clone()));
+    Ok(matcher)
+}
+
+/// Set the number of compiled patterns kept, 0 disables the cache
+#[pyfunction]
+pub fn set_cache_size(size: usize) {
+    with_cache(|cache| {
+        cache.size = size;
+        cache.evict(size);
+    })
+}
+
+/// Hits, misses, maximum and current size of the cache
+#[pyfunction]
+pub fn cache_info() -> HashMap<&'static str, u64> {
+    with_cache(|cache| {
+        HashMap::from([
+            ("hits", cache.hits),
+            ("misses", cache.misses),
+            ("maxsize", cache.size as u64),
+            ("currsize", cache.entries.len() as u64),
+        ])
+    })
+}
+
+/// Drop all compiled patterns and reset the statistics
+#[pyfunction]
+pub fn clear_cache() {
+    with_cache(|cache| {
+        cache.entries.clear();
+        cache.hits = 0;
+        cache.misses = 0;
+    })
+}
@@ -2,8 +2,10 @@ use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum StringpyErr {
     RegexErr(regex::Error),
+    ArrowErr(arrow2::error::Error),
     PyErr(PyErr),
 }
 
@@ -19,17 +21,35 @@ impl From<regex::Error> for StringpyErr {
     }
 }
 
+impl From<arrow2::error::Error> for StringpyErr {
+    fn from(err: arrow2::error::Error) -> Self {
+        StringpyErr::ArrowErr(err)
+    }
+}
+
 impl From<StringpyErr> for PyErr {
     fn from(string_err: StringpyErr) -> Self {
         match string_err {
             // handle regex parse error
             StringpyErr::RegexErr(err) => PyValueError::new_err(err.to_string()),
+            // handle arrow error, e.g. invalid dictionary keys
+            StringpyErr::ArrowErr(err) => PyValueError::new_err(err.to_string()),
             // handle pyo3 error
             StringpyErr::PyErr(err) => err,
         }
     }
 }
 
+impl From<stringpy_core::Error> for StringpyErr {
+    fn from(err: stringpy_core::Error) -> Self {
+        match err {
+            stringpy_core::Error::RegexErr(err) => StringpyErr::RegexErr(err),
+            stringpy_core::Error::ArrowErr(err) => StringpyErr::ArrowErr(err),
+            stringpy_core::Error::ValueErr(message) => StringpyErr::new_value_err(message),
+        }
+    }
+}
+
 impl From<PyErr> for StringpyErr {
     fn from(err: PyErr) -> Self {
         StringpyErr::PyErr(err)
@@ -1,5 +1,8 @@
 mod arrow_in;
-mod atomic;
+mod cache;
 mod error;
+mod parallel;
+mod pattern;
 pub mod str;
+mod stream;
 mod utils;
@@ -0,0 +1,40 @@
+//! Thread pool the kernels run on once the GIL is released.
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::{Arc, Mutex};
+
+static POOL: Mutex<Option<Arc<ThreadPool>>> = Mutex::new(None);
+
+fn build_pool(num_threads: usize) -> PyResult<Arc<ThreadPool>> {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+    Ok(Arc::new(pool))
+}
+
+/// The pool kernels run on. It is created on first use with rayon's default size:
+/// `RAYON_NUM_THREADS` if set, otherwise one thread per core.
+pub fn thread_pool() -> PyResult<Arc<ThreadPool>> {
+    let mut pool = POOL.lock().unwrap();
+    if pool.is_none() {
+        *pool = Some(build_pool(0)?);
+    }
+    Ok(pool.as_ref().unwrap().clone())
+}
+
+/// Set the number of threads used by the kernels, 0 restores the default
+#[pyfunction]
+pub fn set_num_threads(num_threads: usize) -> PyResult<()> {
+    let pool = build_pool(num_threads)?;
+    *POOL.lock().unwrap() = Some(pool);
+    Ok(())
+}
+
+/// Get the number of threads used by the kernels
+#[pyfunction]
+pub fn get_num_threads() -> PyResult<usize> {
+    Ok(thread_pool()?.current_num_threads())
+}
@@ -0,0 +1,277 @@
+//! Compiled patterns, passed to the `str_*` functions in place of a plain string pattern.
+
+use crate::cache;
+use crate::error::StringpyErr;
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+use std::collections::HashMap;
+use std::sync::Arc;
+use stringpy_core::pattern::{self, Anchor, Matcher, MatcherSet, Options};
+
+/// A pattern compiled once with its options, reusable across calls
+#[pyclass(module = "stringpy._stringpy", frozen)]
+#[derive(Clone)]
+pub struct Pattern {
+    source: String,
+    options: Options,
+    matcher: Arc<Matcher>,
+}
+
+impl Pattern {
+    fn compile(source: String, options: Options) -> Result<Self, StringpyErr> {
+        let matcher = Arc::new(Matcher::new(&source, options)?);
+        Ok(Pattern {
+            source,
+            options,
+            matcher,
+        })
+    }
+}
+
+#[pymethods]
+impl Pattern {
+    #[new]
+    #[pyo3(signature = (
+        source,
+        fixed=false,
+        ignore_case=false,
+        multiline=false,
+        dotall=false,
+        comments=false,
+        unicode=true
+    ))]
+    fn new(
+        source: String,
+        fixed: bool,
+        ignore_case: bool,
+        multiline: bool,
+        dotall: bool,
+        comments: bool,
+        unicode: bool,
+    ) -> PyResult<Self> {
+        let options = Options {
+            fixed,
+            ignore_case,
+            multiline,
+            dotall,
+            comments,
+            unicode,
+        };
+        Ok(Pattern::compile(source, options)?)
+    }
+
+    /// The pattern as given
+    #[getter]
+    fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the pattern is matched as a literal string
+    #[getter]
+    fn fixed(&self) -> bool {
+        self.options.fixed
+    }
+
+    #[getter]
+    fn ignore_case(&self) -> bool {
+        self.options.ignore_case
+    }
+
+    #[getter]
+    fn multiline(&self) -> bool {
+        self.options.multiline
+    }
+
+    #[getter]
+    fn dotall(&self) -> bool {
+        self.options.dotall
+    }
+
+    #[getter]
+    fn comments(&self) -> bool {
+        self.options.comments
+    }
+
+    #[getter]
+    fn unicode(&self) -> bool {
+        self.options.unicode
+    }
+
+    /// Number of groups, including the implicit group 0 of the whole match
+    #[getter]
+    fn captures_len(&self) -> usize {
+        self.matcher.captures_len()
+    }
+
+    /// Name of each group, `None` for unnamed ones
+    #[getter]
+    fn group_names(&self) -> Vec<Option<&str>> {
+        self.matcher.group_names()
+    }
+
+    /// Rebuild from the source and options, the compiled pattern itself is not serialized
+    fn __reduce__(&self, py: Python) -> (PyObject, PyObject) {
+        let options = self.options;
+        let args: Py<PyTuple> = (
+            &self.source,
+            options.fixed,
+            options.ignore_case,
+            options.multiline,
+            options.dotall,
+            options.comments,
+            options.unicode,
+        )
+            .into_py(py);
+        (py.get_type::<Pattern>().into(), args.into())
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let options = self.options;
+        let default = Options::default();
+        let source = self.source.to_object(py);
+        let mut args = vec![source.as_ref(py).repr()?.to_string()];
+        for (name, value, default) in [
+            ("ignore_case", options.ignore_case, default.ignore_case),
+            ("multiline", options.multiline, default.multiline),
+            ("dotall", options.dotall, default.dotall),
+            ("comments", options.comments, default.comments),
+            ("unicode", options.unicode, default.unicode),
+        ] {
+            if value != default {
+                args.push(format!("{}={}", name, if value { "True" } else { "False" }));
+            }
+        }
+        let func = if options.fixed { "fixed" } else { "regex" };
+        Ok(format!("{}({})", func, args.join(", ")))
+    }
+}
+
+/// Match `pattern` as a literal string, without the regex engine
+#[pyfunction]
+#[pyo3(signature = (pattern, ignore_case=false))]
+pub fn fixed(pattern: String, ignore_case: bool) -> PyResult<Pattern> {
+    let options = Options {
+        fixed: true,
+        ignore_case,
+        ..Options::default()
+    };
+    Ok(Pattern::compile(pattern, options)?)
+}
+
+/// Match `pattern` as a regex built with the given flags
+#[pyfunction]
+#[pyo3(signature = (
+    pattern,
+    ignore_case=false,
+    multiline=false,
+    dotall=false,
+    comments=false,
+    unicode=true
+))]
+pub fn regex(
+    pattern: String,
+    ignore_case: bool,
+    multiline: bool,
+    dotall: bool,
+    comments: bool,
+    unicode: bool,
+) -> PyResult<Pattern> {
+    Pattern::new(
+        pattern,
+        false,
+        ignore_case,
+        multiline,
+        dotall,
+        comments,
+        unicode,
+    )
+}
+
+/// A pattern argument: a plain string or a [`Pattern`]
+#[derive(FromPyObject)]
+pub enum PatternArg<'a> {
+    Pattern(Pattern),
+    Str(&'a str),
+}
+
+impl PatternArg<'_> {
+    fn source(&self) -> &str {
+        match self {
+            PatternArg::Pattern(pattern) => &pattern.source,
+            PatternArg::Str(source) => source,
+        }
+    }
+
+    /// A plain string takes its options from the arguments of the function
+    fn options(&self, fixed: bool) -> Options {
+        match self {
+            PatternArg::Pattern(pattern) => pattern.options,
+            PatternArg::Str(_) => Options {
+                fixed,
+                ..Options::default()
+            },
+        }
+    }
+}
+
+/// Compile a single pattern, through the cache for a plain string
+pub fn compile_one(pattern: &PatternArg, fixed: bool) -> Result<Arc<Matcher>, StringpyErr> {
+    match pattern {
+        PatternArg::Pattern(pattern) => Ok(pattern.matcher.clone()),
+        PatternArg::Str(source) => cache::compile(source, pattern.options(fixed)),
+    }
+}
+
+/// Compile per-row patterns, each distinct plain string once
+pub fn compile(pattern: &[PatternArg], fixed: bool) -> Result<Vec<Arc<Matcher>>, StringpyErr> {
+    let mut compiled: HashMap<&str, Arc<Matcher>> = HashMap::new();
+    pattern
+        .iter()
+        .map(|x| match x {
+            PatternArg::Str(source) => {
+                if let Some(matcher) = compiled.get(source) {
+                    return Ok(matcher.clone());
+                }
+                let matcher = compile_one(x, fixed)?;
+                compiled.insert(source, matcher.clone());
+                Ok(matcher)
+            }
+            PatternArg::Pattern(_) => compile_one(x, fixed),
+        })
+        .collect()
+}
+
+/// Compile per-row patterns only matching at `anchor`, each distinct one once.
+/// A plain string is a literal prefix or suffix.
+pub fn compile_anchored(
+    pattern: &[PatternArg],
+    anchor: Anchor,
+) -> Result<Vec<Arc<Matcher>>, StringpyErr> {
+    let mut compiled: HashMap<(&str, Options), Arc<Matcher>> = HashMap::new();
+    pattern
+        .iter()
+        .map(|x| {
+            let key = (x.source(), x.options(true));
+            if let Some(matcher) = compiled.get(&key) {
+                return Ok(matcher.clone());
+            }
+            let (source, options) = pattern::anchored(key.0, key.1, anchor);
+            let matcher = cache::compile(&source, options)?;
+            compiled.insert(key, matcher.clone());
+            Ok(matcher)
+        })
+        .collect()
+}
+
+/// Compile patterns searched together, they must all share the same options
+pub fn compile_set(patterns: &[PatternArg], fixed: bool) -> Result<MatcherSet, StringpyErr> {
+    let options = patterns.first().map(|x| x.options(fixed));
+    if patterns.iter().any(|x| Some(x.options(fixed)) != options) {
+        return Err(StringpyErr::new_value_err(
+            "Patterns searched together must share the same options",
+        ));
+    }
+    let options = options.unwrap_or_default();
+    let sources: Vec<&str> = patterns.iter().map(|x| x.source()).collect();
+    Ok(MatcherSet::new(&sources, options)?)
+}
@@ -0,0 +1,150 @@
+//! Streaming execution: apply a `str_*` function to one column of an Arrow C stream,
+//! one record batch at a time, so memory stays bounded by the batch size.
+
+use crate::arrow_in;
+use crate::error::StringpyErr;
+use arrow2::array::{new_empty_array, Array, StructArray};
+use arrow2::datatypes::{DataType, Field};
+use arrow2::error::Error;
+use arrow2::ffi;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// The per-batch operation: call `func` on column `column` and put the result back in place
+struct Transform {
+    column: usize,
+    data_type: DataType,
+    func: PyObject,
+    kwargs: Option<Py<PyDict>>,
+}
+
+impl Transform {
+    fn name(&self) -> &str {
+        match &self.data_type {
+            DataType::Struct(fields) => &fields[self.column].name,
+            _ => "",
+        }
+    }
+
+    fn call(&self, array: Box<dyn Array>, py: Python) -> PyResult<Box<dyn Array>> {
+        let array = arrow_in::to_py_array(array, py)?;
+        let kwargs = self.kwargs.as_ref().map(|kwargs| kwargs.as_ref(py));
+        let result = self.func.call(py, (array,), kwargs)?;
+        arrow_in::to_rust_array(result, py)
+    }
+
+    fn apply(&self, batch: Box<dyn Array>, py: Python) -> Result<Box<dyn Array>, StringpyErr> {
+        let batch = batch.as_any().downcast_ref::<StructArray>().unwrap();
+        let mut values = batch.values().to_vec();
+        values[self.column] = self.call(values[self.column].clone(), py)?;
+
+        let batch =
+            StructArray::try_new(self.data_type.clone(), values, batch.validity().cloned())?;
+        Ok(batch.boxed())
+    }
+}
+
+/// Record batches of a stream, transformed lazily as they are pulled.
+/// Iterate over it to get `pa.RecordBatch`, or hand it to any consumer of
+/// `__arrow_c_stream__`, e.g. `pa.RecordBatchReader.from_stream`.
+#[pyclass(module = "stringpy._stringpy", unsendable)]
+pub struct BatchStream {
+    reader: Option<ffi::ArrowArrayStreamReader>,
+    field: Field,
+    transform: Transform,
+}
+
+#[pymethods]
+impl BatchStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(None);
+        };
+        let Some(batch) = (unsafe { reader.next() }) else {
+            return Ok(None);
+        };
+
+        let batch = batch.map_err(StringpyErr::from)?;
+        let batch = self.transform.apply(batch, py)?;
+        arrow_in::to_py_record_batch(batch, py).map(Some)
+    }
+
+    /// Export the remaining batches, the stream can only be consumed once
+    #[pyo3(signature = (requested_schema=None))]
+    fn __arrow_c_stream__(
+        &mut self,
+        py: Python,
+        requested_schema: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let _ = requested_schema;
+        let mut reader = self.reader.take().ok_or(StringpyErr::new_value_err(
+            "Stream has already been consumed",
+        ))?;
+        let transform = Transform {
+            column: self.transform.column,
+            data_type: self.transform.data_type.clone(),
+            func: self.transform.func.clone_ref(py),
+            kwargs: self.transform.kwargs.clone(),
+        };
+
+        // consumers only see the message of the error, keep the column and the Python error in it
+        let context = format!(" transforming column `{}`", transform.name());
+        let batches = std::iter::from_fn(move || {
+            let batch = unsafe { reader.next() }?;
+            Some(batch.and_then(|batch| {
+                Python::with_gil(|py| transform.apply(batch, py))
+                    .map_err(|err| Error::External(context.clone(), Box::new(PyErr::from(err))))
+            }))
+        });
+        let stream = ffi::export_iterator(Box::new(batches), self.field.clone());
+        arrow_in::new_capsule(py, stream, "arrow_array_stream")
+    }
+}
+
+/// Apply `func` (one of the `str_*` functions) to `column` of every record batch in `reader`.
+/// Extra keyword arguments are passed to `func`. Batches are read and transformed lazily.
+#[pyfunction]
+#[pyo3(signature = (reader, column, func, **kwargs))]
+pub fn str_stream(
+    py: Python,
+    reader: PyObject,
+    column: &str,
+    func: PyObject,
+    kwargs: Option<Py<PyDict>>,
+) -> PyResult<BatchStream> {
+    let reader = arrow_in::to_rust_stream(reader, py)?;
+    let input = reader.field().clone();
+    let DataType::Struct(mut fields) = input.data_type.clone() else {
+        return Err(StringpyErr::new_value_err("Expect a stream of record batches").into());
+    };
+    let index =
+        fields
+            .iter()
+            .position(|field| field.name == column)
+            .ok_or(StringpyErr::new_value_err(format!(
+                "Column `{}` does not exist in stream",
+                column
+            )))?;
+
+    let mut transform = Transform {
+        column: index,
+        data_type: DataType::Null,
+        func,
+        kwargs,
+    };
+    // the output schema must be known before any batch is read,
+    // so run `func` on an empty column to get its output type
+    let empty = transform.call(new_empty_array(fields[index].data_type.clone()), py)?;
+    fields[index] = Field::new(fields[index].name.clone(), empty.data_type().clone(), true);
+    transform.data_type = DataType::Struct(fields);
+
+    Ok(BatchStream {
+        reader: Some(reader),
+        field: Field::new(input.name, transform.data_type.clone(), input.is_nullable),
+        transform,
+    })
+}
@@ -1,138 +1,125 @@
 use crate::arrow_in;
-use arrow2::array::Utf8Array;
+use crate::error::StringpyErr;
+use crate::parallel;
+use arrow2::array::{new_empty_array, Array};
 use pyo3::prelude::*;
-
-/// Converts a Python list of strings to a Utf8Array
-pub fn list_array(ob: PyObject, py: Python) -> Utf8Array<i32> {
-    let array = arrow_in::to_rust_array(ob, py).unwrap();
-    let array = array.as_any();
-    let array = array.downcast_ref::<Utf8Array<i32>>().unwrap();
-    array.to_owned()
-}
-
-/// Apply a function to a Utf8Array and return a new Utf8Array.
-/// This function must take one element of input and return one element of output
-///
-#[macro_export]
-macro_rules! apply_utf8 {
-    ($ob:expr; $func:expr; $($args:expr),* ) => {
-        {
-
-    let result = Python::with_gil(|py| {
-        let array = arrow_in::to_rust_array($ob, py).unwrap();
-        let array = array.as_any();
-        let array: Vec<Option<Cow<str>>> = array
-            .downcast_ref::<Utf8Array<i32>>()
-            .ok_or(StringpyErr::new_value_err("Expect string array"))?
-            .iter()
-            .map(|i| $func(i, $($args),*))
-            .collect();
-
-        let result = arrow2::array::Utf8Array::<i32>::from(array);
-        let result = Box::new(result);
-        arrow_in::to_py_array(result, py)
-    });
-    Ok(result?)
-
+use stringpy_core::kernel::Broadcast;
+use stringpy_core::Error;
+
+/// Converts a Python list of strings to arrow arrays, one per chunk
+pub fn list_array(ob: PyObject, py: Python) -> PyResult<Vec<Box<dyn Array>>> {
+    if arrow_in::is_chunked(&ob, py)? {
+        Ok(arrow_in::to_rust_chunks(ob, py)?.1)
+    } else {
+        Ok(vec![arrow_in::to_rust_array(ob, py)?])
     }
-};
-($ob:expr ;  $func:expr ; $($ob2:ident),* ; $($args:expr),* ) => {
-    {
-
-let result = Python::with_gil(|py| {
-    let array = arrow_in::to_rust_array($ob, py).unwrap();
-    let array = array.as_any();
-    let array= array
-        .downcast_ref::<Utf8Array<i32>>()
-        .ok_or(StringpyErr::new_value_err("Expect string array"))?;
-
-    $(let $ob2 =  if $ob2.len() == 1 {
-        vec![$ob2[0]; array.len()]
-     } else {$ob2};)*
-
-    let array: Vec<Option<Cow<str>>> = izip!(array, $($ob2),*)
-        .map(|(i1,   $($ob2),*) | $func(i1,  $($ob2),* ,  $($args),*))
-        //.map(|(i1, width, side)| $func(i1,width, side,  $($args),*))
-        .collect();
-
-    let result = arrow2::array::Utf8Array::<i32>::from(array);
-    let result = Box::new(result);
-    arrow_in::to_py_array(result, py)
-});
-Ok(result?)
-
-}
-}
 }
 
-#[macro_export]
-macro_rules!  apply_utf8_bool {
-    ($ob:expr; $func:expr; $($args:expr),* ) => {
-        {
+/// The rows of the whole input a chunk covers, to take the matching part of the per-row arguments
+#[derive(Clone, Copy, Debug)]
+pub struct Rows {
+    offset: usize,
+    len: usize,
+    total: usize,
+}
 
-    let result = Python::with_gil(|py| {
-        let array = arrow_in::to_rust_array($ob, py)?;
-        let array = array.as_any();
-        let array: Vec<Option<bool>> = array
-            .downcast_ref::<Utf8Array<i32>>()
-            .ok_or(StringpyErr::new_value_err("Expect string array"))?
-            .iter()
-            .map(|i| $func(i, $($args),*))
-            .collect();
+impl Rows {
+    /// Index of the first row of the chunk in the whole input
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 
-        let result = arrow2::array::BooleanArray::from(array);
-        let result = Box::new(result);
-        arrow_in::to_py_array(result, py)
-    });
-    Ok(result?)
+    /// The part of the per-row argument `x` covering the chunk, after checking with
+    /// [`Broadcast::new`] that it fits the whole input. A single value is recycled over all
+    /// chunks, `name` is only used in the error message.
+    pub fn of<'a, T: Copy>(&self, name: &str, x: &'a [T]) -> Result<&'a [T], Error> {
+        let x = Broadcast::new(name, x, self.total)?;
+        if x.is_scalar() {
+            Ok(x.values())
+        } else {
+            Ok(&x.values()[self.offset..self.offset + self.len])
+        }
     }
 }
-}
-
-#[macro_export]
-macro_rules! apply_utf8_i32 {
-    ($ob:expr; $func:expr; $($args:expr),* ) => {
-        {
-
-    let result = Python::with_gil(|py| {
-        let array = arrow_in::to_rust_array($ob, py).unwrap();
-        let array = array.as_any();
-        let array: Vec<Option<i32>> = array
-            .downcast_ref::<Utf8Array<i32>>()
-            .ok_or(StringpyErr::new_value_err("Expect string array"))?
-            .iter()
-            .map(|i| $func(i, $($args),*))
-            .collect();
-
-        let result = arrow2::array::Int32Array::from(array);
-        let result = Box::new(result);
-        arrow_in::to_py_array(result, py)
-    });
-    Ok(result?)
 
-    }};
-($ob:expr ;  $func:expr ; $($ob2:ident),* ; $($args:expr),* ) => {
-    {
-
-let result = Python::with_gil(|py| {
-    let array = arrow_in::to_rust_array($ob, py).unwrap();
-    let array = array.as_any();
-    let array= array
-        .downcast_ref::<Utf8Array<i32>>()
-        .ok_or(StringpyErr::new_value_err("Expect string array"))?;
-
-    let array: Vec<Option<i32>> = izip!(array, $($ob2),*)
-        .map(|(i1, $($ob2),*) | $func(i1,  $($ob2),* ,  $($args),*))
-        .collect();
+/// Import `ob` and run `kernel` on it. A chunked input is processed chunk by chunk and
+/// returned as a `pa.ChunkedArray` with the same chunk layout, any other input is returned as a `pa.Array`.
+/// Only the import and export hold the GIL, the kernel runs without it on the thread pool.
+/// `kernel` gets the [`Rows`] each chunk covers, to take its part of the per-row arguments.
+pub fn apply_chunks<F, E>(ob: PyObject, py: Python, mut kernel: F) -> PyResult<PyObject>
+where
+    F: FnMut(Box<dyn Array>, Rows) -> Result<Box<dyn Array>, E> + Send,
+    E: Into<StringpyErr> + Send,
+{
+    let pool = parallel::thread_pool()?;
+    if !arrow_in::is_chunked(&ob, py)? {
+        let array = arrow_in::to_rust_array(ob, py)?;
+        let rows = Rows {
+            offset: 0,
+            len: array.len(),
+            total: array.len(),
+        };
+        let result = py
+            .allow_threads(|| pool.install(|| kernel(array, rows)))
+            .map_err(Into::into)?;
+        return arrow_in::to_py_array(result, py);
+    }
 
-    let result = arrow2::array::Int32Array::from(array);
-    let result = Box::new(result);
-    arrow_in::to_py_array(result, py)
-});
-Ok(result?)
+    let (data_type, chunks) = arrow_in::to_rust_chunks(ob, py)?;
+    let total = chunks.iter().map(|chunk| chunk.len()).sum();
+    if chunks.is_empty() {
+        // nothing to compute, run the kernel on an empty array only to get the output type
+        let rows = Rows {
+            offset: 0,
+            len: 0,
+            total,
+        };
+        let data_type = kernel(new_empty_array(data_type), rows)
+            .map_err(Into::into)?
+            .data_type()
+            .clone();
+        return arrow_in::to_py_chunked_array(chunks, data_type, py);
+    }
 
+    let chunks = py
+        .allow_threads(|| {
+            pool.install(|| {
+                let mut offset = 0;
+                chunks
+                    .into_iter()
+                    .map(|chunk| {
+                        let rows = Rows {
+                            offset,
+                            len: chunk.len(),
+                            total,
+                        };
+                        offset += rows.len;
+                        kernel(chunk, rows)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+        })
+        .map_err(Into::into)?;
+    let data_type = chunks[0].data_type().clone();
+    arrow_in::to_py_chunked_array(chunks, data_type, py)
 }
+
+/// Same as [`apply_chunks`] with no per-row argument, acquiring the GIL first
+pub fn apply<F, E>(ob: PyObject, mut kernel: F) -> PyResult<PyObject>
+where
+    F: FnMut(Box<dyn Array>) -> Result<Box<dyn Array>, E> + Send,
+    E: Into<StringpyErr> + Send,
+{
+    apply_rows(ob, |array, _| kernel(array))
 }
+
+/// Same as [`apply_chunks`], acquiring the GIL first
+pub fn apply_rows<F, E>(ob: PyObject, kernel: F) -> PyResult<PyObject>
+where
+    F: FnMut(Box<dyn Array>, Rows) -> Result<Box<dyn Array>, E> + Send,
+    E: Into<StringpyErr> + Send,
+{
+    Python::with_gil(|py| apply_chunks(ob, py, kernel))
 }
 
 #[allow(unused_macros)]
@@ -175,8 +162,7 @@ macro_rules! assert_array_utf8 {
         }};
     }
 
-pub(crate) use apply_utf8;
-pub(crate) use apply_utf8_bool;
-pub(crate) use apply_utf8_i32;
+#[allow(unused_imports)]
 pub(crate) use assert_array_i32;
+#[allow(unused_imports)]
 pub(crate) use assert_array_utf8;
@@ -0,0 +1,10 @@
+use crate::pattern::Matcher;
+
+pub fn detect(x: &str, pat: &Matcher, negate: bool) -> bool {
+    let a = pat.is_match(x);
+    if negate {
+        !a
+    } else {
+        a
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    RegexErr(regex::Error),
+    ArrowErr(arrow2::error::Error),
+    ValueErr(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn new_value_err<S: Into<String>>(message: S) -> Self {
+        Error::ValueErr(message.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RegexErr(err) => err.fmt(f),
+            Error::ArrowErr(err) => err.fmt(f),
+            Error::ValueErr(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Self {
+        Error::RegexErr(err)
+    }
+}
+
+impl From<arrow2::error::Error> for Error {
+    fn from(err: arrow2::error::Error) -> Self {
+        Error::ArrowErr(err)
+    }
+}
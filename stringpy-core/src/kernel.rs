@@ -0,0 +1,599 @@
+//! The kernel framework: run a function on every row of a string array and build the output.
+//!
+//! A kernel only deals with one non-null string at a time, [`map`] and [`map_rows`] take care of
+//! the rest: downcasting the input, null propagation, dictionary-encoded inputs, running the rows
+//! in parallel, building the output with the offset width of the input and returning the first
+//! error by row order. The shape of the output is picked with an [`Output`]:
+//!
+//! ```
+//! use arrow2::array::{BooleanArray, Utf8Array};
+//! use stringpy_core::kernel::{map, Boolean};
+//!
+//! let array = Utf8Array::<i32>::from([Some("abc"), None, Some("")]);
+//! let result = map(&array, &Boolean, |x| Ok(Some(x.is_empty()))).unwrap();
+//! let result = result.as_any().downcast_ref::<BooleanArray>().unwrap();
+//! assert_eq!(result, &BooleanArray::from([Some(false), None, Some(true)]));
+//! ```
+
+use crate::error::{Error, Result};
+use crate::parallel;
+use crate::utils;
+use arrow2::array::{
+    Array, BooleanArray, ListArray, MutableArray, MutablePrimitiveArray, MutableUtf8Array,
+    PrimitiveArray, StructArray, Utf8Array,
+};
+use arrow2::bitmap::{Bitmap, MutableBitmap};
+use arrow2::datatypes::{DataType, Field};
+use arrow2::offset::{Offset, Offsets, OffsetsBuffer};
+use arrow2::types::NativeType;
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+/// Shape of the output of a kernel
+pub trait Output: Sync {
+    /// Value a kernel computes for one row
+    type Value<'a>;
+    /// Builder of the output, for an input with offset width `O`
+    type Builder<O: Offset>: Builder<Self>;
+    /// Whether rows returned unchanged can be taken from the input instead of being copied
+    const PASSTHROUGH: bool = false;
+
+    /// A builder for `rows` rows, of an input holding `bytes` bytes of strings
+    fn builder<O: Offset>(&self, rows: usize, bytes: usize) -> Self::Builder<O>;
+
+    /// Whether `value` is the input row itself
+    fn is_input(_input: &str, _value: &Self::Value<'_>) -> bool {
+        false
+    }
+}
+
+/// Builds an output array row by row
+pub trait Builder<T: Output + ?Sized>: Send + Sized {
+    fn push(&mut self, value: Option<T::Value<'_>>);
+
+    /// Push an input row unchanged, only called with a non-null row when `T::PASSTHROUGH`
+    fn push_input(&mut self, input: Option<&str>) {
+        assert!(input.is_none(), "output cannot hold input rows");
+        self.push(None)
+    }
+
+    /// Append the rows of `parts` after the rows of `self`, each buffer grows once for all of them
+    fn extend(&mut self, parts: Vec<Self>);
+
+    fn finish(self) -> Box<dyn Array>;
+}
+
+/// Append the validity of parts of `(validity, rows)` to the validity of `len` rows, growing it
+/// once for all the parts. It stays `None` as long as all rows are valid.
+fn extend_validity<'a, I>(validity: &mut Option<MutableBitmap>, len: usize, parts: I)
+where
+    I: Iterator<Item = (Option<&'a MutableBitmap>, usize)> + Clone,
+{
+    if validity.is_none() && parts.clone().all(|(other, _)| other.is_none()) {
+        return;
+    }
+    let rows = parts.clone().map(|(_, rows)| rows).sum();
+    let validity = validity.get_or_insert_with(|| {
+        let mut validity = MutableBitmap::with_capacity(len + rows);
+        validity.extend_constant(len, true);
+        validity
+    });
+    validity.reserve(rows);
+    for (other, rows) in parts {
+        match other {
+            Some(other) => validity.extend_from_slice(other.as_slice(), 0, rows),
+            None => validity.extend_constant(rows, true),
+        }
+    }
+}
+
+/// String output, with the offset width of the input
+pub struct Utf8;
+
+pub struct Utf8Builder<O: Offset>(MutableUtf8Array<O>);
+
+impl Output for Utf8 {
+    type Value<'a> = Cow<'a, str>;
+    type Builder<O: Offset> = Utf8Builder<O>;
+    const PASSTHROUGH: bool = true;
+
+    fn builder<O: Offset>(&self, rows: usize, bytes: usize) -> Utf8Builder<O> {
+        Utf8Builder(MutableUtf8Array::with_capacities(rows, bytes))
+    }
+
+    fn is_input(input: &str, value: &Cow<str>) -> bool {
+        matches!(value, Cow::Borrowed(x) if x.as_ptr() == input.as_ptr() && x.len() == input.len())
+    }
+}
+
+impl<O: Offset> Builder<Utf8> for Utf8Builder<O> {
+    fn push(&mut self, value: Option<Cow<str>>) {
+        self.0.push(value)
+    }
+
+    fn push_input(&mut self, input: Option<&str>) {
+        self.0.push(input)
+    }
+
+    fn extend(&mut self, parts: Vec<Self>) {
+        let len = self.0.len();
+        let (data_type, mut offsets, mut values, mut validity) =
+            std::mem::take(&mut self.0).into_data();
+
+        values.reserve(parts.iter().map(|part| part.0.values().len()).sum());
+        offsets.reserve(parts.iter().map(|part| part.0.len()).sum());
+        let mut validities = Vec::with_capacity(parts.len());
+        for part in parts {
+            let rows = part.0.len();
+            let (_, other_offsets, other_values, other_validity) = part.0.into_data();
+            values.extend_from_slice(&other_values);
+            offsets
+                .try_extend_from_slice(&other_offsets.into(), 0, rows)
+                .expect("offsets overflow");
+            validities.push((other_validity, rows));
+        }
+        let validities = validities
+            .iter()
+            .map(|(other, rows)| (other.as_ref(), *rows));
+        extend_validity(&mut validity, len, validities);
+        // every part is valid utf8 and its offsets were shifted to the end of `values`
+        self.0 = unsafe { MutableUtf8Array::new_unchecked(data_type, offsets, values, validity) };
+    }
+
+    fn finish(self) -> Box<dyn Array> {
+        let array: Utf8Array<O> = self.0.into();
+        array.boxed()
+    }
+}
+
+/// Boolean output
+pub struct Boolean;
+
+pub struct BooleanBuilder {
+    values: MutableBitmap,
+    validity: MutableBitmap,
+}
+
+impl Output for Boolean {
+    type Value<'a> = bool;
+    type Builder<O: Offset> = BooleanBuilder;
+
+    fn builder<O: Offset>(&self, rows: usize, _bytes: usize) -> BooleanBuilder {
+        BooleanBuilder {
+            values: MutableBitmap::with_capacity(rows),
+            validity: MutableBitmap::with_capacity(rows),
+        }
+    }
+}
+
+impl Builder<Boolean> for BooleanBuilder {
+    fn push(&mut self, value: Option<bool>) {
+        self.values.push(value.unwrap_or_default());
+        self.validity.push(value.is_some());
+    }
+
+    fn extend(&mut self, parts: Vec<Self>) {
+        let rows = parts.iter().map(|part| part.values.len()).sum();
+        self.values.reserve(rows);
+        self.validity.reserve(rows);
+        for part in parts {
+            let len = part.values.len();
+            self.values
+                .extend_from_slice(part.values.as_slice(), 0, len);
+            self.validity
+                .extend_from_slice(part.validity.as_slice(), 0, len);
+        }
+    }
+
+    fn finish(self) -> Box<dyn Array> {
+        let validity = Bitmap::from(self.validity);
+        let validity = (validity.unset_bits() > 0).then_some(validity);
+        BooleanArray::new(DataType::Boolean, self.values.into(), validity).boxed()
+    }
+}
+
+/// Integer output
+pub struct Primitive<T>(PhantomData<T>);
+
+pub type Int32 = Primitive<i32>;
+pub type Int64 = Primitive<i64>;
+
+impl<T> Primitive<T> {
+    pub const fn new() -> Self {
+        Primitive(PhantomData)
+    }
+}
+
+impl<T> Default for Primitive<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct PrimitiveBuilder<T: NativeType>(MutablePrimitiveArray<T>);
+
+impl<T: NativeType> Output for Primitive<T> {
+    type Value<'a> = T;
+    type Builder<O: Offset> = PrimitiveBuilder<T>;
+
+    fn builder<O: Offset>(&self, rows: usize, _bytes: usize) -> PrimitiveBuilder<T> {
+        PrimitiveBuilder(MutablePrimitiveArray::with_capacity(rows))
+    }
+}
+
+impl<T: NativeType> Builder<Primitive<T>> for PrimitiveBuilder<T> {
+    fn push(&mut self, value: Option<T>) {
+        self.0.push(value)
+    }
+
+    fn extend(&mut self, parts: Vec<Self>) {
+        let len = self.0.len();
+        let (data_type, mut values, mut validity) = std::mem::take(&mut self.0).into_inner();
+
+        values.reserve(parts.iter().map(|part| part.0.len()).sum());
+        let mut validities = Vec::with_capacity(parts.len());
+        for part in parts {
+            let (_, other_values, other_validity) = part.0.into_inner();
+            values.extend_from_slice(&other_values);
+            validities.push((other_validity, other_values.len()));
+        }
+        let validities = validities
+            .iter()
+            .map(|(other, rows)| (other.as_ref(), *rows));
+        extend_validity(&mut validity, len, validities);
+        self.0 = MutablePrimitiveArray::try_new(data_type, values, validity).unwrap();
+    }
+
+    fn finish(self) -> Box<dyn Array> {
+        let array: PrimitiveArray<T> = self.0.into();
+        array.boxed()
+    }
+}
+
+/// List output, with the offset width of the input. A null row becomes a null list, or a list of
+/// nulls with [`List::with_null_slots`].
+///
+/// ```
+/// use arrow2::array::{ListArray, Utf8Array};
+/// use std::borrow::Cow;
+/// use stringpy_core::kernel::{map, List, Utf8};
+///
+/// let array = Utf8Array::<i32>::from_iter_values(std::iter::repeat("a,b").take(5000));
+/// let result = map(&array, &List::new(Utf8), |x| {
+///     Ok(Some(x.split(',').map(|x| Some(Cow::from(x))).collect()))
+/// })
+/// .unwrap();
+/// let result = result.as_any().downcast_ref::<ListArray<i32>>().unwrap();
+/// assert_eq!(result.len(), 5000);
+/// assert_eq!(result.values().len(), 10000);
+/// ```
+pub struct List<T> {
+    pub values: T,
+    pub null: NullRow,
+}
+
+/// What a null row becomes in a [`List`] output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NullRow {
+    /// A null list
+    Null,
+    /// A list of this many nulls
+    Slots(usize),
+}
+
+impl<T> List<T> {
+    pub const fn new(values: T) -> Self {
+        List {
+            values,
+            null: NullRow::Null,
+        }
+    }
+
+    /// Null rows become lists of `slots` nulls, e.g. `[None, None]` for a `(start, end)` pair
+    pub const fn with_null_slots(values: T, slots: usize) -> Self {
+        List {
+            values,
+            null: NullRow::Slots(slots),
+        }
+    }
+}
+
+pub struct ListBuilder<O: Offset, T: Output> {
+    values: T::Builder<O>,
+    lengths: Vec<usize>,
+    validity: MutableBitmap,
+    null: NullRow,
+}
+
+impl<T: Output> Output for List<T> {
+    type Value<'a> = Vec<Option<T::Value<'a>>>;
+    type Builder<O: Offset> = ListBuilder<O, T>;
+
+    fn builder<O: Offset>(&self, rows: usize, bytes: usize) -> ListBuilder<O, T> {
+        ListBuilder {
+            values: self.values.builder(rows, bytes),
+            lengths: Vec::with_capacity(rows),
+            validity: MutableBitmap::with_capacity(rows),
+            null: self.null,
+        }
+    }
+}
+
+impl<O: Offset, T: Output> Builder<List<T>> for ListBuilder<O, T> {
+    fn push(&mut self, value: Option<Vec<Option<T::Value<'_>>>>) {
+        self.validity
+            .push(value.is_some() || self.null != NullRow::Null);
+        match (value, self.null) {
+            (Some(value), _) => {
+                self.lengths.push(value.len());
+                value.into_iter().for_each(|x| self.values.push(x));
+            }
+            (None, NullRow::Null) => self.lengths.push(0),
+            (None, NullRow::Slots(slots)) => {
+                self.lengths.push(slots);
+                (0..slots).for_each(|_| self.values.push(None));
+            }
+        }
+    }
+
+    fn extend(&mut self, parts: Vec<Self>) {
+        let rows = parts.iter().map(|part| part.lengths.len()).sum();
+        self.lengths.reserve(rows);
+        self.validity.reserve(rows);
+        let mut values = Vec::with_capacity(parts.len());
+        for part in parts {
+            self.lengths.extend(part.lengths);
+            let len = part.validity.len();
+            self.validity
+                .extend_from_slice(part.validity.as_slice(), 0, len);
+            values.push(part.values);
+        }
+        self.values.extend(values);
+    }
+
+    fn finish(self) -> Box<dyn Array> {
+        let values = self.values.finish();
+        let field = Box::new(Field::new("_", values.data_type().clone(), true));
+        let data_type = if O::IS_LARGE {
+            DataType::LargeList(field)
+        } else {
+            DataType::List(field)
+        };
+
+        let offsets = OffsetsBuffer::from(Offsets::<O>::try_from_iter(self.lengths).unwrap());
+        let validity = Bitmap::from(self.validity);
+        let validity = (validity.unset_bits() > 0).then_some(validity);
+        ListArray::<O>::new(data_type, offsets, values, validity).boxed()
+    }
+}
+
+/// Struct output, all fields of the same output. A kernel returns one value per field.
+pub struct Struct<T> {
+    pub names: Vec<String>,
+    pub field: T,
+}
+
+pub struct StructBuilder<O: Offset, T: Output> {
+    names: Vec<String>,
+    fields: Vec<T::Builder<O>>,
+    validity: Vec<bool>,
+}
+
+impl<T: Output> Output for Struct<T> {
+    type Value<'a> = Vec<Option<T::Value<'a>>>;
+    type Builder<O: Offset> = StructBuilder<O, T>;
+
+    fn builder<O: Offset>(&self, rows: usize, bytes: usize) -> StructBuilder<O, T> {
+        StructBuilder {
+            names: self.names.clone(),
+            fields: self
+                .names
+                .iter()
+                .map(|_| self.field.builder(rows, bytes))
+                .collect(),
+            validity: Vec::with_capacity(rows),
+        }
+    }
+}
+
+impl<O: Offset, T: Output> Builder<Struct<T>> for StructBuilder<O, T> {
+    fn push(&mut self, value: Option<Vec<Option<T::Value<'_>>>>) {
+        self.validity.push(value.is_some());
+        match value {
+            Some(value) => {
+                assert_eq!(value.len(), self.fields.len(), "one value per field");
+                self.fields
+                    .iter_mut()
+                    .zip(value)
+                    .for_each(|(field, x)| field.push(x));
+            }
+            None => self.fields.iter_mut().for_each(|field| field.push(None)),
+        }
+    }
+
+    fn extend(&mut self, parts: Vec<Self>) {
+        self.validity
+            .reserve(parts.iter().map(|part| part.validity.len()).sum());
+        let mut fields: Vec<Vec<T::Builder<O>>> = self
+            .fields
+            .iter()
+            .map(|_| Vec::with_capacity(parts.len()))
+            .collect();
+        for part in parts {
+            self.validity.extend(part.validity);
+            fields
+                .iter_mut()
+                .zip(part.fields)
+                .for_each(|(field, other)| field.push(other));
+        }
+        self.fields
+            .iter_mut()
+            .zip(fields)
+            .for_each(|(field, parts)| field.extend(parts));
+    }
+
+    fn finish(self) -> Box<dyn Array> {
+        let values: Vec<Box<dyn Array>> = self.fields.into_iter().map(|x| x.finish()).collect();
+        let fields = self
+            .names
+            .into_iter()
+            .zip(&values)
+            .map(|(name, x)| Field::new(name, x.data_type().clone(), true))
+            .collect();
+        let validity = Bitmap::from_iter(self.validity);
+        StructArray::new(DataType::Struct(fields), values, Some(validity)).boxed()
+    }
+}
+
+/// A per-row argument of a kernel: either one value recycled over all rows, or one value per row
+pub struct Broadcast<'a, T>(&'a [T]);
+
+impl<'a, T: Copy> Broadcast<'a, T> {
+    /// Check `values` fits an array of `len` rows, `name` is only used in the error message
+    pub fn new(name: &str, values: &'a [T], len: usize) -> Result<Self> {
+        if values.len() == 1 || values.len() == len {
+            Ok(Broadcast(values))
+        } else {
+            Err(Error::new_value_err(format!(
+                "Length of `{}` must be equal to 1 or to length of array",
+                name
+            )))
+        }
+    }
+
+    /// Value for row `row`
+    pub fn get(&self, row: usize) -> T {
+        if self.0.len() == 1 {
+            self.0[0]
+        } else {
+            self.0[row]
+        }
+    }
+
+    /// Whether all rows share one value
+    pub fn is_scalar(&self) -> bool {
+        self.0.len() == 1
+    }
+
+    /// The values as given
+    pub fn values(&self) -> &'a [T] {
+        self.0
+    }
+}
+
+/// Run `f` on every non-null string of `array`, null rows stay null.
+/// `f` must not depend on the row, so a dictionary-encoded input only runs it once per dictionary value.
+pub fn map<T, F>(array: &dyn Array, output: &T, f: F) -> Result<Box<dyn Array>>
+where
+    T: Output,
+    F: for<'a> Fn(&'a str) -> Result<Option<T::Value<'a>>> + Sync,
+{
+    utils::apply_dictionary(
+        array,
+        |array| crate::with_utf8!(array; _O, array => run(array, output, |x, _| f(x))),
+    )
+}
+
+/// Same as [`map`], `f` also gets the row index, e.g. to read a [`Broadcast`] argument.
+/// A dictionary-encoded input is decoded first.
+pub fn map_rows<T, F>(array: &dyn Array, output: &T, f: F) -> Result<Box<dyn Array>>
+where
+    T: Output,
+    F: for<'a> Fn(&'a str, usize) -> Result<Option<T::Value<'a>>> + Sync,
+{
+    let decoded = utils::decode_dictionary(array)?;
+    let array = decoded.as_deref().unwrap_or(array);
+    crate::with_utf8!(array; _O, array => run(array, output, f))
+}
+
+/// Run `f` with [`map`] when `scalar`, i.e. every per-row argument holds a single value,
+/// otherwise with [`map_rows`]. `f` gets row 0 in the first case.
+pub fn map_args<T, F>(array: &dyn Array, output: &T, scalar: bool, f: F) -> Result<Box<dyn Array>>
+where
+    T: Output,
+    F: for<'a> Fn(&'a str, usize) -> Result<Option<T::Value<'a>>> + Sync,
+{
+    if scalar {
+        map(array, output, |x| f(x, 0))
+    } else {
+        map_rows(array, output, f)
+    }
+}
+
+fn run<O, T, F>(array: &Utf8Array<O>, output: &T, f: F) -> Result<Box<dyn Array>>
+where
+    O: Offset,
+    T: Output,
+    F: for<'a> Fn(&'a str, usize) -> Result<Option<T::Value<'a>>> + Sync,
+{
+    let offsets = array.offsets().buffer();
+    let bytes = |start: usize, end: usize| offsets[end].to_usize() - offsets[start].to_usize();
+    // the rows of a task left unchanged, for a passthrough output
+    let input = |rows: Range<usize>| {
+        let mut part = output.builder::<O>(rows.len(), bytes(rows.start, rows.end));
+        rows.for_each(|i| part.push_input(array.get(i)));
+        part
+    };
+
+    // The output is allocated once, sized after the input, when a first task changes a row. Each
+    // wave of tasks is appended to it and dropped before the next wave runs.
+    let mut result: Option<T::Builder<O>> = None;
+    let task = |rows: Range<usize>| {
+        // with a passthrough output, a task only starts building at its first changed row
+        let mut builder: Option<T::Builder<O>> = None;
+        for i in rows.clone() {
+            let input = array.get(i);
+            let value = match input {
+                Some(x) => f(x, i)?,
+                None => None,
+            };
+            if builder.is_none() {
+                let unchanged = match (input, &value) {
+                    (None, None) => true,
+                    (Some(x), Some(value)) => T::is_input(x, value),
+                    _ => false,
+                };
+                if T::PASSTHROUGH && unchanged {
+                    continue;
+                }
+                let mut new = output.builder::<O>(rows.len(), bytes(rows.start, rows.end));
+                (rows.start..i).for_each(|j| new.push_input(array.get(j)));
+                builder = Some(new);
+            }
+            builder.as_mut().unwrap().push(value);
+        }
+        Ok::<_, Error>(builder)
+    };
+    parallel::map_tasks(array.len(), task, |wave| -> Result<()> {
+        let mut wave = wave
+            .into_iter()
+            .map(|(rows, part)| Ok((rows, part?)))
+            .collect::<Result<Vec<_>>>()?;
+        if result.is_none() {
+            if wave.iter().all(|(_, part)| part.is_none()) {
+                return Ok(());
+            }
+            if wave.len() == 1 && wave[0].0.len() == array.len() {
+                result = wave.pop().unwrap().1;
+                return Ok(());
+            }
+            // the rows of the previous waves are all unchanged
+            let mut new = output.builder::<O>(array.len(), bytes(0, array.len()));
+            (0..wave[0].0.start).for_each(|i| new.push_input(array.get(i)));
+            result = Some(new);
+        }
+        let parts = wave
+            .into_iter()
+            .map(|(rows, part)| part.unwrap_or_else(|| input(rows)))
+            .collect();
+        result.as_mut().unwrap().extend(parts);
+        Ok(())
+    })?;
+
+    Ok(match result {
+        Some(result) => result.finish(),
+        None if T::PASSTHROUGH => array.clone().boxed(),
+        None => output.builder::<O>(0, 0).finish(),
+    })
+}
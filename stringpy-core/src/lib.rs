@@ -0,0 +1,30 @@
+//! String kernels of stringpy, on plain `arrow2` arrays.
+//!
+//! Every `str_*` function takes a `Utf8`, `LargeUtf8` or dictionary-encoded string array and
+//! returns a new array, with the same semantics as the Python package:
+//!
+//! ```
+//! use arrow2::array::Utf8Array;
+//! use stringpy_core::str::str_sub;
+//! use stringpy_core::unicode::Unit;
+//!
+//! let array = Utf8Array::<i32>::from([Some("stringpy"), None]);
+//! let result = str_sub(&array, &[-2], &[8], Unit::Graphemes).unwrap();
+//! let result = result.as_any().downcast_ref::<Utf8Array<i32>>().unwrap();
+//! assert_eq!(result, &Utf8Array::<i32>::from([Some("py"), None]));
+//! ```
+//!
+//! Rows are processed in parallel on the current rayon thread pool.
+
+pub use arrow2;
+
+pub mod atomic;
+pub mod error;
+pub mod kernel;
+pub mod parallel;
+pub mod pattern;
+pub mod str;
+pub mod unicode;
+pub mod utils;
+
+pub use error::{Error, Result};
@@ -0,0 +1,41 @@
+//! Row-parallel building blocks of the kernels. They run on the current rayon thread pool,
+//! install another pool around the calls to control the number of threads.
+
+use rayon::prelude::*;
+use std::ops::Range;
+
+/// Minimum number of rows handled by one task, smaller inputs are not worth splitting
+const MIN_ROWS_PER_TASK: usize = 1024;
+
+/// Number of waves the tasks of a kernel are run in, see [`map_tasks`]
+const WAVES: usize = 4;
+
+/// Split the rows `0..len` into contiguous ranges and compute `f` on each range in parallel.
+/// There is always at least one range.
+///
+/// The ranges run in waves of one range per thread, `g` gets the results of a wave in row order
+/// before the next wave runs, so that only one wave of results is alive at a time. The first
+/// error returned by `g` stops the remaining waves.
+pub fn map_tasks<T, E, F, G>(len: usize, f: F, mut g: G) -> Result<(), E>
+where
+    T: Send,
+    F: Fn(Range<usize>) -> T + Sync + Send,
+    G: FnMut(Vec<(Range<usize>, T)>) -> Result<(), E>,
+{
+    let threads = rayon::current_num_threads();
+    let rows_per_task = len.div_ceil(threads * WAVES).max(MIN_ROWS_PER_TASK);
+    let tasks = len.div_ceil(rows_per_task).max(1);
+
+    for wave in (0..tasks).step_by(threads) {
+        let results = (wave..(wave + threads).min(tasks))
+            .into_par_iter()
+            .map(|task| {
+                let start = task * rows_per_task;
+                let rows = start..(start + rows_per_task).min(len);
+                (rows.clone(), f(rows))
+            })
+            .collect();
+        g(results)?;
+    }
+    Ok(())
+}
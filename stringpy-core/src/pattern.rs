@@ -0,0 +1,338 @@
+//! Compiled patterns, given to the kernels once or per row. Rows sharing a pattern share
+//! one compiled [`Matcher`], so each distinct pattern only needs to be compiled once.
+//! A pattern is a regular expression, or a literal string with [`Options::fixed`], which is
+//! searched with `memchr` and never goes through the regex engine unless it ignores case.
+
+use crate::error::{Error, Result};
+use crate::kernel::Broadcast;
+use aho_corasick::AhoCorasick;
+use memchr::memmem::Finder;
+use regex::{escape, NoExpand, Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// How patterns are matched. The flags map onto the ones of [`RegexBuilder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Options {
+    /// Match the pattern as a literal string instead of a regex
+    pub fixed: bool,
+    pub ignore_case: bool,
+    /// `^` and `$` match at the start and end of each line
+    pub multiline: bool,
+    /// `.` also matches `\n`
+    pub dotall: bool,
+    /// Ignore whitespace and allow `#` comments in the pattern
+    pub comments: bool,
+    /// Unicode aware classes, e.g. `\w` matches `é`
+    pub unicode: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            fixed: false,
+            ignore_case: false,
+            multiline: false,
+            dotall: false,
+            comments: false,
+            unicode: true,
+        }
+    }
+}
+
+/// Where a pattern has to match, for prefixes and suffixes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    Start,
+    End,
+}
+
+/// The source and options of a pattern only matching at `anchor`, to build a [`Matcher`] for
+/// [`Matcher::is_at`]. A fixed pattern is kept as is, unless it ignores case or is empty, as
+/// it then goes through the regex engine.
+pub fn anchored(pattern: &str, options: Options, anchor: Anchor) -> (Cow<'_, str>, Options) {
+    if options.fixed && !options.ignore_case && !pattern.is_empty() {
+        return (Cow::Borrowed(pattern), options);
+    }
+    let (pattern, options) = if options.fixed {
+        let options = Options {
+            ignore_case: options.ignore_case,
+            ..Options::default()
+        };
+        (escape(pattern), options)
+    } else {
+        (pattern.to_string(), options)
+    };
+    // a `#` comment runs to the end of the line, it must not swallow the closing parenthesis
+    let close = if options.comments { "\n)" } else { ")" };
+    let pattern = match anchor {
+        Anchor::Start => format!("\\A(?:{}{}", pattern, close),
+        Anchor::End => format!("(?:{}{}\\z", pattern, close),
+    };
+    (Cow::Owned(pattern), options)
+}
+
+/// A compiled pattern
+pub enum Matcher {
+    Regex(Regex),
+    Fixed(Finder<'static>),
+    /// A fixed pattern ignoring case, searched as an escaped regex
+    FixedCaseless(Regex),
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, options: Options) -> Result<Self> {
+        if !options.fixed {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(options.ignore_case)
+                .multi_line(options.multiline)
+                .dot_matches_new_line(options.dotall)
+                .ignore_whitespace(options.comments)
+                .unicode(options.unicode)
+                .build()?;
+            return Ok(Matcher::Regex(regex));
+        }
+        if pattern.is_empty() {
+            return Err(Error::new_value_err("A fixed pattern can not be empty"));
+        }
+        if options.ignore_case {
+            let regex = RegexBuilder::new(&escape(pattern))
+                .case_insensitive(true)
+                .build()?;
+            return Ok(Matcher::FixedCaseless(regex));
+        }
+        Ok(Matcher::Fixed(Finder::new(pattern).into_owned()))
+    }
+
+    /// The pattern as given, escaped for a fixed pattern ignoring case
+    pub fn as_str(&self) -> &str {
+        match self {
+            Matcher::Regex(re) | Matcher::FixedCaseless(re) => re.as_str(),
+            // the needle comes from a `&str`
+            Matcher::Fixed(finder) => std::str::from_utf8(finder.needle()).unwrap(),
+        }
+    }
+
+    /// Number of groups, including the implicit group of the whole match
+    pub fn captures_len(&self) -> usize {
+        match self {
+            Matcher::Regex(re) => re.captures_len(),
+            Matcher::Fixed(_) | Matcher::FixedCaseless(_) => 1,
+        }
+    }
+
+    /// Name of each group, `None` for unnamed ones
+    pub fn group_names(&self) -> Vec<Option<&str>> {
+        match self {
+            Matcher::Regex(re) => re.capture_names().collect(),
+            Matcher::Fixed(_) | Matcher::FixedCaseless(_) => vec![None],
+        }
+    }
+
+    /// Whether `x` starts or ends with a match, the pattern must have been built from [`anchored`]
+    pub fn is_at(&self, x: &str, anchor: Anchor) -> bool {
+        match (self, anchor) {
+            (Matcher::Regex(re) | Matcher::FixedCaseless(re), _) => re.is_match(x),
+            (Matcher::Fixed(finder), Anchor::Start) => x.as_bytes().starts_with(finder.needle()),
+            (Matcher::Fixed(finder), Anchor::End) => x.as_bytes().ends_with(finder.needle()),
+        }
+    }
+
+    pub fn is_match(&self, x: &str) -> bool {
+        match self {
+            Matcher::Regex(re) | Matcher::FixedCaseless(re) => re.is_match(x),
+            Matcher::Fixed(finder) => finder.find(x.as_bytes()).is_some(),
+        }
+    }
+
+    /// Start and end of the first match
+    pub fn find(&self, x: &str) -> Option<(usize, usize)> {
+        match self {
+            Matcher::Regex(re) | Matcher::FixedCaseless(re) => {
+                re.find(x).map(|m| (m.start(), m.end()))
+            }
+            Matcher::Fixed(finder) => finder
+                .find(x.as_bytes())
+                .map(|start| (start, start + finder.needle().len())),
+        }
+    }
+
+    /// Start and end of all non-overlapping matches
+    pub fn find_iter<'a>(&'a self, x: &'a str) -> Box<dyn Iterator<Item = (usize, usize)> + 'a> {
+        match self {
+            Matcher::Regex(re) | Matcher::FixedCaseless(re) => {
+                Box::new(re.find_iter(x).map(|m| (m.start(), m.end())))
+            }
+            Matcher::Fixed(finder) => {
+                let len = finder.needle().len();
+                Box::new(finder.find_iter(x.as_bytes()).map(move |i| (i, i + len)))
+            }
+        }
+    }
+
+    /// Replace the first `limit` matches, all of them if `limit` is 0.
+    /// The replacement is literal for a fixed pattern, and can refer to groups for a regex.
+    pub fn replacen<'a>(&self, x: &'a str, limit: usize, replace: &str) -> Cow<'a, str> {
+        match self {
+            Matcher::Regex(re) => return re.replacen(x, limit, replace),
+            Matcher::FixedCaseless(re) => return re.replacen(x, limit, NoExpand(replace)),
+            Matcher::Fixed(_) => {}
+        }
+
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let mut matches = self.find_iter(x).take(limit).peekable();
+        if matches.peek().is_none() {
+            return Cow::Borrowed(x);
+        }
+        let mut result = String::with_capacity(x.len());
+        let mut last = 0;
+        for (start, end) in matches {
+            result.push_str(&x[last..start]);
+            result.push_str(replace);
+            last = end;
+        }
+        result.push_str(&x[last..]);
+        Cow::Owned(result)
+    }
+
+    /// Pieces of `x` around the matches, at most `n`
+    pub fn splitn<'a>(&self, x: &'a str, n: usize) -> Vec<&'a str> {
+        if let Matcher::Regex(re) | Matcher::FixedCaseless(re) = self {
+            return re.splitn(x, n).collect();
+        }
+        if n == 0 {
+            return vec![];
+        }
+
+        let mut pieces = Vec::new();
+        let mut last = 0;
+        for (start, end) in self.find_iter(x).take(n - 1) {
+            pieces.push(&x[last..start]);
+            last = end;
+        }
+        pieces.push(&x[last..]);
+        pieces
+    }
+}
+
+/// Per-row patterns, remembering whether all rows share one
+pub struct PatternTable<'a> {
+    pattern: Broadcast<'a, &'a Matcher>,
+    distinct: Vec<&'a Matcher>,
+}
+
+impl<'a> PatternTable<'a> {
+    /// The patterns of an array of `len` rows
+    pub fn new(pattern: &'a [&'a Matcher], len: usize) -> Result<Self> {
+        let pattern = Broadcast::new("pattern", pattern, len)?;
+        let mut seen = HashSet::new();
+        let distinct = pattern
+            .values()
+            .iter()
+            .copied()
+            .filter(|x| seen.insert(*x as *const Matcher))
+            .collect();
+        Ok(PatternTable { pattern, distinct })
+    }
+
+    /// Pattern of row `row`
+    pub fn get(&self, row: usize) -> &Matcher {
+        self.pattern.get(row)
+    }
+
+    /// Whether all rows share one pattern
+    pub fn is_scalar(&self) -> bool {
+        self.distinct.len() == 1
+    }
+
+    /// Distinct patterns
+    pub fn matchers(&self) -> impl Iterator<Item = &Matcher> {
+        self.distinct.iter().copied()
+    }
+}
+
+/// Several patterns searched together, each string is scanned once for all of them
+pub enum MatcherSet {
+    Regex(RegexSet),
+    Fixed(AhoCorasick),
+}
+
+impl MatcherSet {
+    /// Fixed patterns are searched with Aho-Corasick, unless they ignore case
+    pub fn new(patterns: &[&str], options: Options) -> Result<Self> {
+        if options.fixed && patterns.iter().any(|x| x.is_empty()) {
+            return Err(Error::new_value_err("A fixed pattern can not be empty"));
+        }
+        if options.fixed && !options.ignore_case {
+            let set =
+                AhoCorasick::new(patterns).map_err(|err| Error::new_value_err(err.to_string()))?;
+            return Ok(MatcherSet::Fixed(set));
+        }
+
+        let patterns: Vec<Cow<str>> = patterns
+            .iter()
+            .map(|x| {
+                if options.fixed {
+                    Cow::Owned(escape(x))
+                } else {
+                    Cow::Borrowed(*x)
+                }
+            })
+            .collect();
+        let set = RegexSetBuilder::new(patterns)
+            .case_insensitive(options.ignore_case)
+            .multi_line(options.multiline)
+            .dot_matches_new_line(options.dotall)
+            .ignore_whitespace(options.comments && !options.fixed)
+            .unicode(options.unicode)
+            .build()?;
+        Ok(MatcherSet::Regex(set))
+    }
+
+    /// Number of patterns
+    pub fn len(&self) -> usize {
+        match self {
+            MatcherSet::Regex(set) => set.len(),
+            MatcherSet::Fixed(set) => set.patterns_len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether any pattern matches
+    pub fn is_match(&self, x: &str) -> bool {
+        match self {
+            MatcherSet::Regex(set) => set.is_match(x),
+            MatcherSet::Fixed(set) => set.is_match(x),
+        }
+    }
+
+    /// Index of the matching patterns, in increasing order
+    pub fn matches(&self, x: &str) -> Vec<usize> {
+        match self {
+            MatcherSet::Regex(set) => set.matches(x).into_iter().collect(),
+            MatcherSet::Fixed(set) => {
+                let mut matches: Vec<usize> = set
+                    .find_overlapping_iter(x)
+                    .map(|m| m.pattern().as_usize())
+                    .collect();
+                matches.sort_unstable();
+                matches.dedup();
+                matches
+            }
+        }
+    }
+
+    /// Index of the first pattern, in the order given, that matches
+    pub fn first(&self, x: &str) -> Option<usize> {
+        match self {
+            MatcherSet::Regex(set) => set.matches(x).into_iter().next(),
+            MatcherSet::Fixed(set) => set
+                .find_overlapping_iter(x)
+                .map(|m| m.pattern().as_usize())
+                .min(),
+        }
+    }
+}
@@ -0,0 +1,730 @@
+//! The `str_*` kernels. Every kernel takes a `Utf8` or `LargeUtf8` array, or a dictionary-encoded
+//! one, and the output keeps the offset width of the input.
+//! Arguments given as slices are per-row: either one value recycled over all rows,
+//! or one value per row.
+
+use crate::atomic;
+use crate::error::{Error, Result};
+use crate::kernel::{self, Boolean, Broadcast, Int32, List, Utf8};
+use crate::pattern::{Anchor, Matcher, MatcherSet, PatternTable};
+use crate::unicode::{self, Form, Locale, Unit, Units, WidthMode};
+use crate::utils;
+use arrow2::array::{Array, Int32Array, Utf8Array};
+use itertools::Itertools;
+use std::borrow::Cow;
+
+/// Collapse all rows of a string column, given as chunks, into a single string
+pub fn str_c(chunks: &[Box<dyn Array>], collapse: &str) -> Result<String> {
+    let chunks = utils::decode_chunks(chunks)?;
+    let mut result = utils::utf8_chunks_iter(&chunks)?
+        .map(|i| {
+            let mut val = i.unwrap_or("").to_string();
+            val.push_str(collapse);
+            val
+        })
+        .reduce(|x, y| x + &y)
+        .unwrap_or_default();
+    let len = collapse.len();
+    for _ in 0..len {
+        result.pop();
+    }
+
+    Ok(result)
+}
+
+/// Combine several string columns, each given as chunks, row by row
+pub fn str_combine(columns: &[Vec<Box<dyn Array>>], sep: &str) -> Result<Vec<String>> {
+    let Some((last_element, a)) = columns.split_last() else {
+        return Ok(vec![]);
+    };
+
+    let len = last_element.iter().map(|chunk| chunk.len()).sum();
+    let rs: Vec<String> = vec![String::with_capacity(50); len];
+
+    fn combine_two(mut x1: Vec<String>, x2: &[Box<dyn Array>], sep: &str) -> Result<Vec<String>> {
+        let x2 = utils::decode_chunks(x2)?;
+        x1.iter_mut()
+            .zip(utils::utf8_chunks_iter(&x2)?)
+            .for_each(|(v1, v2)| {
+                v1.push_str(v2.unwrap());
+                v1.push_str(sep);
+            });
+        Ok(x1)
+    }
+
+    let rs = a.iter().try_fold(rs, |x1, x2| combine_two(x1, x2, sep))?;
+    combine_two(rs, last_element, "")
+}
+
+pub fn str_count(array: &dyn Array, pattern: &[&Matcher]) -> Result<Box<dyn Array>> {
+    let pattern = PatternTable::new(pattern, array.len())?;
+
+    // decide if vectorize or not
+    kernel::map_args(array, &Int32::new(), pattern.is_scalar(), |x, i| {
+        Ok(Some(pattern.get(i).find_iter(x).count() as i32))
+    })
+}
+
+/// Replace the first match of `pattern` with `replace`, both given per row
+pub fn str_replace(
+    array: &dyn Array,
+    pattern: &[&Matcher],
+    replace: &[&str],
+) -> Result<Box<dyn Array>> {
+    replacen(array, pattern, replace, 1)
+}
+
+/// Replace all matches of `pattern` with `replace`, both given per row
+pub fn str_replace_all(
+    array: &dyn Array,
+    pattern: &[&Matcher],
+    replace: &[&str],
+) -> Result<Box<dyn Array>> {
+    replacen(array, pattern, replace, 0)
+}
+
+fn replacen(
+    array: &dyn Array,
+    pattern: &[&Matcher],
+    replace: &[&str],
+    limit: usize,
+) -> Result<Box<dyn Array>> {
+    let pattern = PatternTable::new(pattern, array.len())?;
+    let replace = Broadcast::new("replace", replace, array.len())?;
+
+    let scalar = pattern.is_scalar() && replace.is_scalar();
+    kernel::map_args(array, &Utf8, scalar, |x, i| {
+        Ok(Some(pattern.get(i).replacen(x, limit, replace.get(i))))
+    })
+}
+
+pub fn str_remove(array: &dyn Array, pattern: &[&Matcher]) -> Result<Box<dyn Array>> {
+    str_replace(array, pattern, &[""])
+}
+
+pub fn str_remove_all(array: &dyn Array, pattern: &[&Matcher]) -> Result<Box<dyn Array>> {
+    str_replace_all(array, pattern, &[""])
+}
+
+pub fn str_squish(array: &dyn Array) -> Result<Box<dyn Array>> {
+    fn squish(x: &str) -> Cow<'_, str> {
+        let a: Vec<_> = x.split_whitespace().collect();
+        let a = a.join(" ");
+        if a == x {
+            return Cow::Borrowed(x);
+        }
+        Cow::from(a)
+    }
+    kernel::map(array, &Utf8, |x| Ok(Some(squish(x))))
+}
+
+pub fn str_trim(array: &dyn Array, side: &str) -> Result<Box<dyn Array>> {
+    let trim = match side {
+        "left" => str::trim_start,
+        "right" => str::trim_end,
+        "both" => str::trim,
+        _ => {
+            return Err(Error::new_value_err(
+                "side must be one of 'left', 'right', 'both'",
+            ))
+        }
+    };
+    kernel::map(array, &Utf8, |x| Ok(Some(Cow::from(trim(x)))))
+}
+
+pub fn str_detect(array: &dyn Array, pattern: &[&Matcher]) -> Result<Box<dyn Array>> {
+    let pattern = PatternTable::new(pattern, array.len())?;
+    kernel::map_args(array, &Boolean, pattern.is_scalar(), |x, i| {
+        Ok(Some(pattern.get(i).is_match(x)))
+    })
+}
+
+/// Whether strings match any of `patterns`, scanning each string once for all of them
+pub fn str_detect_any(array: &dyn Array, patterns: &MatcherSet) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Boolean, |x| Ok(Some(patterns.is_match(x))))
+}
+
+/// Index of the `patterns` matching each string, scanning each string once for all of them.
+/// Null rows are null lists.
+pub fn str_which_patterns(array: &dyn Array, patterns: &MatcherSet) -> Result<Box<dyn Array>> {
+    kernel::map(array, &List::new(Int32::new()), |x| {
+        let matches = patterns.matches(x).into_iter();
+        Ok(Some(matches.map(|i| Some(i as i32)).collect()))
+    })
+}
+
+/// Label of the first of `patterns` matching each string, or `default` when none matches
+pub fn str_case_when(
+    array: &dyn Array,
+    patterns: &MatcherSet,
+    labels: &[&str],
+    default: Option<&str>,
+) -> Result<Box<dyn Array>> {
+    if labels.len() != patterns.len() {
+        return Err(Error::new_value_err("There must be one label per pattern"));
+    }
+
+    kernel::map(array, &Utf8, |x| {
+        let label = patterns.first(x).map(|i| labels[i]).or(default);
+        Ok(label.map(|x| Cow::Owned(x.to_string())))
+    })
+}
+
+/// Remove accents, keeping the base letters in their own script
+pub fn str_remove_ascent(array: &dyn Array) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Utf8, |x| Ok(Some(unicode::remove_accents(x))))
+}
+
+/// Transliterate to ASCII, e.g. `Ёлка` becomes `Iolka` and `北京` becomes `Bei Jing `
+pub fn str_transliterate(array: &dyn Array) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Utf8, |x| {
+        Ok(Some(Cow::from(unidecode::unidecode(x))))
+    })
+}
+
+/// Normalize strings to `form`
+pub fn str_normalize(array: &dyn Array, form: Form) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Utf8, |x| Ok(Some(unicode::normalize(x, form))))
+}
+
+/// Truncate strings longer than `width` units, keeping the `left`, `right` or both ends (`center`).
+/// In [`WidthMode::Display`], `width` counts columns and strings are cut between graphemes.
+pub fn str_trunc(
+    array: &dyn Array,
+    width: usize,
+    side: &str,
+    ellipsis: &str,
+    unit: Unit,
+    mode: WidthMode,
+) -> Result<Box<dyn Array>> {
+    if !["left", "right", "center"].contains(&side) {
+        return Err(Error::new_value_err(
+            "side must be one of 'left', 'right', 'center'",
+        ));
+    }
+
+    fn truncate<'a>(
+        x: &'a str,
+        width: usize,
+        side: &str,
+        ellipsis: &str,
+        unit: Unit,
+    ) -> Cow<'a, str> {
+        let units = Units::new(x, unit);
+        let len_x = units.len();
+        if len_x < width {
+            return Cow::from(x);
+        }
+
+        let a = match side {
+            "left" => format!("{}{}", units.slice(0, width), ellipsis),
+            "right" => format!("{}{}", ellipsis, units.slice(len_x - width, len_x)),
+            _ => {
+                let middle = (width / 2) as f32;
+                let first = middle.round() as usize;
+                let tail = width - middle as usize;
+                let first = units.slice(0, first);
+                let tail = units.slice(len_x - tail, len_x);
+                format!("{}{}{}", first, ellipsis, tail)
+            }
+        };
+        Cow::from(a)
+    }
+
+    fn truncate_display<'a>(x: &'a str, width: usize, side: &str, ellipsis: &str) -> Cow<'a, str> {
+        if unicode::width(x) < width {
+            return Cow::from(x);
+        }
+
+        let a = match side {
+            "left" => format!("{}{}", unicode::prefix(x, width), ellipsis),
+            "right" => format!("{}{}", ellipsis, unicode::suffix(x, width)),
+            _ => {
+                let first = unicode::prefix(x, width / 2);
+                let tail = unicode::suffix(x, width - width / 2);
+                format!("{}{}{}", first, ellipsis, tail)
+            }
+        };
+        Cow::from(a)
+    }
+
+    kernel::map(array, &Utf8, |x| {
+        Ok(Some(match mode {
+            WidthMode::Length => truncate(x, width, side, ellipsis, unit),
+            WidthMode::Display => truncate_display(x, width, side, ellipsis),
+        }))
+    })
+}
+
+fn check_group(pattern: &PatternTable, group: Option<usize>) -> Result<()> {
+    if let Some(grp) = group {
+        for pat in pattern.matchers() {
+            if grp >= pat.captures_len() {
+                return Err(Error::new_value_err(format!(
+                    "Group {} does not exist in `{}`",
+                    grp,
+                    pat.as_str()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn str_extract(
+    array: &dyn Array,
+    pattern: &[&Matcher],
+    group: Option<usize>,
+) -> Result<Box<dyn Array>> {
+    let pattern = PatternTable::new(pattern, array.len())?;
+    check_group(&pattern, group)?;
+
+    kernel::map_args(array, &Utf8, pattern.is_scalar(), |x, i| {
+        Ok(match (pattern.get(i), group) {
+            (Matcher::Regex(pat), Some(grp)) => pat
+                .captures(x)
+                .map(|x| Cow::from(x.get(grp).unwrap().as_str())),
+            // a fixed pattern only has group 0, the whole match
+            (pat, _) => pat.find(x).map(|(start, end)| Cow::from(&x[start..end])),
+        })
+    })
+}
+
+pub fn str_extract_all(
+    array: &dyn Array,
+    pattern: &[&Matcher],
+    group: Option<usize>,
+) -> Result<Box<dyn Array>> {
+    let pattern = PatternTable::new(pattern, array.len())?;
+    check_group(&pattern, group)?;
+
+    kernel::map_args(
+        array,
+        &List::with_null_slots(Utf8, 1),
+        pattern.is_scalar(),
+        |x, i| {
+            Ok(Some(match (pattern.get(i), group) {
+                (Matcher::Regex(pat), Some(grp)) => pat
+                    .captures_iter(x)
+                    .map(|x| Some(Cow::from(x.get(grp).unwrap().as_str())))
+                    .collect(),
+                (pat, _) => pat
+                    .find_iter(x)
+                    .map(|(start, end)| Some(Cow::from(&x[start..end])))
+                    .collect(),
+            }))
+        },
+    )
+}
+
+/// Split strings around matches of `pattern`, into at most `n` pieces
+pub fn str_split(
+    array: &dyn Array,
+    pattern: &[&Matcher],
+    n: Option<usize>,
+) -> Result<Box<dyn Array>> {
+    let pattern = PatternTable::new(pattern, array.len())?;
+    let n = n.unwrap_or(usize::MAX);
+
+    kernel::map_args(
+        array,
+        &List::with_null_slots(Utf8, 1),
+        pattern.is_scalar(),
+        |x, i| {
+            let pieces = pattern.get(i).splitn(x, n);
+            Ok(Some(
+                pieces.into_iter().map(|x| Some(Cow::from(x))).collect(),
+            ))
+        },
+    )
+}
+
+/// Whether strings start with `pattern`, given per row and built from [`crate::pattern::anchored`]
+pub fn str_starts(array: &dyn Array, pattern: &[&Matcher], negate: bool) -> Result<Box<dyn Array>> {
+    let pattern = PatternTable::new(pattern, array.len())?;
+    kernel::map_args(array, &Boolean, pattern.is_scalar(), |x, i| {
+        Ok(Some(pattern.get(i).is_at(x, Anchor::Start) != negate))
+    })
+}
+
+/// Whether strings end with `pattern`, given per row and built from [`crate::pattern::anchored`]
+pub fn str_ends(array: &dyn Array, pattern: &[&Matcher], negate: bool) -> Result<Box<dyn Array>> {
+    let pattern = PatternTable::new(pattern, array.len())?;
+    kernel::map_args(array, &Boolean, pattern.is_scalar(), |x, i| {
+        Ok(Some(pattern.get(i).is_at(x, Anchor::End) != negate))
+    })
+}
+
+/// Keep the rows matching `pattern`, or not matching it with `negate`. Nulls are dropped.
+pub fn str_subset(array: &dyn Array, pattern: &Matcher, negate: bool) -> Result<Box<dyn Array>> {
+    let decoded = utils::decode_dictionary(array)?;
+    let array = decoded.as_deref().unwrap_or(array);
+    crate::with_utf8!(array; O, array => {
+        let array: Vec<Option<&str>> = array
+            .iter()
+            .filter(|x| x.is_some_and(|x| atomic::detect(x, pattern, negate)))
+            .collect();
+
+        Ok(Utf8Array::<O>::from(array).boxed())
+    })
+}
+
+/// Index of the rows matching `pattern`, or not matching it with `negate`
+pub fn str_which(array: &dyn Array, pattern: &Matcher, negate: bool) -> Result<Box<dyn Array>> {
+    let decoded = utils::decode_dictionary(array)?;
+    let array = decoded.as_deref().unwrap_or(array);
+    let array: Vec<Option<i32>> = utils::utf8_iter(array)?
+        .enumerate()
+        .filter(|(_, x)| x.is_some_and(|x| atomic::detect(x, pattern, negate)))
+        .map(|(i, _)| Some(i as i32))
+        .collect();
+
+    Ok(Int32Array::from(array).boxed())
+}
+
+pub fn str_dup(array: &dyn Array, times: &[usize]) -> Result<Box<dyn Array>> {
+    let times = Broadcast::new("times", times, array.len())?;
+    kernel::map_rows(array, &Utf8, |x, i| {
+        Ok(Some(Cow::Owned(x.repeat(times.get(i)))))
+    })
+}
+
+/// Length of strings in `unit`
+pub fn str_length(array: &dyn Array, unit: Unit) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Int32::new(), |x| {
+        Ok(Some(unicode::length(x, unit) as i32))
+    })
+}
+
+/// Columns strings take up in a monospace font
+pub fn str_width(array: &dyn Array) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Int32::new(), |x| Ok(Some(unicode::width(x) as i32)))
+}
+
+/// Distinct values, in order of first appearance
+pub fn str_unique(array: &dyn Array) -> Result<Box<dyn Array>> {
+    let decoded = utils::decode_dictionary(array)?;
+    let array = decoded.as_deref().unwrap_or(array);
+    crate::with_utf8!(array; O, array => {
+        let array: Vec<Option<&str>> = array.iter().unique().collect();
+        Ok(Utf8Array::<O>::from(array).boxed())
+    })
+}
+
+pub fn str_to_upper(array: &dyn Array, locale: &Locale) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Utf8, |x| Ok(Some(unicode::to_upper(x, locale))))
+}
+
+pub fn str_to_lower(array: &dyn Array, locale: &Locale) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Utf8, |x| Ok(Some(unicode::to_lower(x, locale))))
+}
+
+/// Capitalize each word, words being split on Unicode word boundaries. Words in `keep_lower`,
+/// e.g. `of` or `and`, are lowercased unless they come first.
+pub fn str_to_title(
+    array: &dyn Array,
+    locale: &Locale,
+    lower_rest: bool,
+    keep_lower: &[&str],
+) -> Result<Box<dyn Array>> {
+    let keep_lower = unicode::lower_words(keep_lower, locale);
+    kernel::map(array, &Utf8, |x| {
+        let title = unicode::to_title(x, locale, lower_rest, &keep_lower);
+        Ok(Some(Cow::Owned(title)))
+    })
+}
+
+/// Capitalize each sentence, sentences ending with a Unicode sentence terminator and a space
+pub fn str_to_sentence(
+    array: &dyn Array,
+    locale: &Locale,
+    lower_rest: bool,
+) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Utf8, |x| {
+        let sentence = unicode::to_sentence(x, locale, lower_rest);
+        Ok(Some(Cow::Owned(sentence)))
+    })
+}
+
+/// Full case folding, to compare strings ignoring case
+pub fn str_casefold(array: &dyn Array, locale: &Locale) -> Result<Box<dyn Array>> {
+    kernel::map(array, &Utf8, |x| Ok(Some(unicode::casefold(x, locale))))
+}
+
+/// Whether strings equal `other`, ignoring case with `ignore_case`
+pub fn str_equal(
+    array: &dyn Array,
+    other: &[&str],
+    ignore_case: bool,
+    locale: &Locale,
+) -> Result<Box<dyn Array>> {
+    let other = Broadcast::new("other", other, array.len())?;
+    kernel::map_rows(array, &Boolean, |x, i| {
+        let y = other.get(i);
+        Ok(Some(if ignore_case {
+            unicode::caseless_eq(x, y, locale)
+        } else {
+            x == y
+        }))
+    })
+}
+
+/// Pad strings shorter than `width` units with `pad`, on the `left`, `right` or `both` sides.
+/// With `both`, the extra padding character goes to the right.
+/// In [`WidthMode::Display`], `width` counts columns, and a wide `pad` fills two of them.
+pub fn str_pad(
+    array: &dyn Array,
+    width: &[usize],
+    side: &[&str],
+    pad: &[char],
+    unit: Unit,
+    mode: WidthMode,
+) -> Result<Box<dyn Array>> {
+    fn padding<'a>(
+        x: &'a str,
+        width: usize,
+        side: &str,
+        pad: char,
+        unit: Unit,
+        mode: WidthMode,
+    ) -> Cow<'a, str> {
+        let (lenth, pad_width) = match mode {
+            WidthMode::Length => (unicode::length(x, unit), 1),
+            WidthMode::Display => {
+                let pad_width = unicode::width(pad.encode_utf8(&mut [0; 4]));
+                (unicode::width(x), pad_width.max(1))
+            }
+        };
+        let count = width.saturating_sub(lenth) / pad_width;
+        if count == 0 {
+            return Cow::Borrowed(x);
+        }
+        let repeat = |n: usize| pad.to_string().repeat(n);
+        match side {
+            "left" => Cow::Owned(repeat(count) + x),
+            "right" => Cow::Owned(x.to_string() + &repeat(count)),
+            "both" => Cow::Owned(repeat(count / 2) + x + &repeat(count - count / 2)),
+            _ => Cow::Borrowed(x),
+        }
+    }
+
+    let width = Broadcast::new("width", width, array.len())?;
+    let side = Broadcast::new("side", side, array.len())?;
+    let pad = Broadcast::new("pad", pad, array.len())?;
+    kernel::map_rows(array, &Utf8, |x, i| {
+        Ok(Some(padding(
+            x,
+            width.get(i),
+            side.get(i),
+            pad.get(i),
+            unit,
+            mode,
+        )))
+    })
+}
+
+/// Substring from unit `start` to `end`, negative indices count from the end of the string
+pub fn str_sub(
+    array: &dyn Array,
+    start: &[i32],
+    end: &[i32],
+    unit: Unit,
+) -> Result<Box<dyn Array>> {
+    fn sub(x: &str, start: i32, end: i32, unit: Unit) -> Cow<'_, str> {
+        let x = Units::new(x, unit);
+        let len = x.len();
+
+        let start = if start >= len as i32 {
+            len
+        } else if (start >= 0) & (start < len as i32) {
+            start as usize
+        } else if (start < 0) & (start > -(len as i32)) {
+            len - (-start as usize)
+        } else {
+            0
+        };
+
+        let end = if end >= len as i32 {
+            len
+        } else if (end >= 0) & (end < len as i32) {
+            end as usize
+        } else if (end < 0) & (end > -(len as i32)) {
+            len - (-end as usize)
+        } else {
+            0
+        };
+
+        if start > end {
+            Cow::Borrowed(x.slice(end, start))
+        } else {
+            Cow::Borrowed(x.slice(start, end))
+        }
+    }
+
+    let start = Broadcast::new("start", start, array.len())?;
+    let end = Broadcast::new("end", end, array.len())?;
+    kernel::map_rows(array, &Utf8, |x, i| {
+        Ok(Some(sub(x, start.get(i), end.get(i), unit)))
+    })
+}
+
+/// Capture groups of the first match of `pattern`, which must be a regex
+pub fn str_match(array: &dyn Array, pattern: &Matcher) -> Result<Box<dyn Array>> {
+    let Matcher::Regex(pat) = pattern else {
+        return Err(Error::new_value_err(
+            "str_match needs a regex pattern, not a fixed one",
+        ));
+    };
+
+    kernel::map(array, &List::with_null_slots(Utf8, 1), |x| {
+        // skip group 0 which is implicit group of whole match
+        Ok(pat.captures(x).map(|groups| {
+            groups
+                .iter()
+                .skip(1)
+                .map(|i| Some(Cow::from(i.unwrap().as_str())))
+                .collect()
+        }))
+    })
+}
+
+/// Start and end of the first match of `pattern`, `[None, None]` for no match and for null rows
+pub fn str_locate(array: &dyn Array, pattern: &Matcher) -> Result<Box<dyn Array>> {
+    kernel::map(array, &List::with_null_slots(Int32::new(), 2), |x| {
+        // no match also takes two null slots
+        Ok(Some(match pattern.find(x) {
+            Some((start, end)) => vec![Some(start as i32), Some(end as i32)],
+            None => vec![None, None],
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow2::array::{DictionaryArray, MutableDictionaryArray, MutableUtf8Array, TryExtend};
+
+    fn strings(array: Box<dyn Array>) -> Vec<Option<String>> {
+        utils::utf8_iter(array.as_ref())
+            .unwrap()
+            .map(|x| x.map(str::to_string))
+            .collect()
+    }
+
+    fn expect(values: &[Option<&str>]) -> Vec<Option<String>> {
+        values.iter().map(|x| x.map(str::to_string)).collect()
+    }
+
+    fn dictionary(values: &[Option<&str>]) -> DictionaryArray<i32> {
+        let mut array = MutableDictionaryArray::<i32, MutableUtf8Array<i32>>::new();
+        array.try_extend(values.iter().copied()).unwrap();
+        array.into()
+    }
+
+    #[test]
+    fn sub_counts_negative_indices_from_the_end() {
+        let array = Utf8Array::<i32>::from([Some("stringpy"), Some("ab"), None]);
+        let result = str_sub(&array, &[-2], &[8], Unit::Graphemes).unwrap();
+        assert_eq!(strings(result), expect(&[Some("py"), Some("ab"), None]));
+
+        let result = str_sub(&array, &[-3], &[-1], Unit::Graphemes).unwrap();
+        assert_eq!(strings(result), expect(&[Some("gp"), Some("a"), None]));
+
+        // indices before the start are clamped to it
+        let result = str_sub(&array, &[-20], &[2], Unit::Graphemes).unwrap();
+        assert_eq!(strings(result), expect(&[Some("st"), Some("ab"), None]));
+
+        // a start after the end swaps them
+        let result = str_sub(&array, &[4, 1, 0], &[1, 0, 0], Unit::Graphemes).unwrap();
+        assert_eq!(strings(result), expect(&[Some("tri"), Some("a"), None]));
+
+        let array = Utf8Array::<i32>::from([Some("👍🏽ok")]);
+        let result = str_sub(&array, &[-3], &[-1], Unit::Graphemes).unwrap();
+        assert_eq!(strings(result), expect(&[Some("👍🏽o")]));
+    }
+
+    #[test]
+    fn pad_both_puts_the_extra_character_on_the_right() {
+        let array = Utf8Array::<i32>::from([Some("ab"), Some("abcdef"), None]);
+        let result = str_pad(
+            &array,
+            &[5],
+            &["both"],
+            &['*'],
+            Unit::Graphemes,
+            WidthMode::Length,
+        )
+        .unwrap();
+        assert_eq!(
+            strings(result),
+            expect(&[Some("*ab**"), Some("abcdef"), None])
+        );
+
+        let array = Utf8Array::<i32>::from([Some("你"), Some("a")]);
+        let result = str_pad(
+            &array,
+            &[6, 4],
+            &["both"],
+            &[' '],
+            Unit::Graphemes,
+            WidthMode::Display,
+        )
+        .unwrap();
+        assert_eq!(strings(result), expect(&[Some("  你  "), Some(" a  ")]));
+    }
+
+    #[test]
+    fn trunc_center_keeps_both_ends() {
+        let array = Utf8Array::<i32>::from([Some("abcdefghij"), Some("abc"), None]);
+        let result = str_trunc(
+            &array,
+            5,
+            "center",
+            "...",
+            Unit::Graphemes,
+            WidthMode::Length,
+        )
+        .unwrap();
+        assert_eq!(
+            strings(result),
+            expect(&[Some("ab...hij"), Some("abc"), None])
+        );
+
+        let array = Utf8Array::<i32>::from([Some("你好世界")]);
+        let result = str_trunc(
+            &array,
+            4,
+            "center",
+            "…",
+            Unit::Graphemes,
+            WidthMode::Display,
+        )
+        .unwrap();
+        assert_eq!(strings(result), expect(&[Some("你…界")]));
+    }
+
+    #[test]
+    fn subset_which_and_unique_decode_dictionaries() {
+        let array = dictionary(&[Some("apple"), None, Some("pear"), Some("apple")]);
+        let pattern = Matcher::new("p", Default::default()).unwrap();
+
+        let result = str_subset(&array, &pattern, false).unwrap();
+        assert_eq!(
+            strings(result),
+            expect(&[Some("apple"), Some("pear"), Some("apple")])
+        );
+
+        let ends_with_e = Matcher::new("e$", Default::default()).unwrap();
+        let result = str_which(&array, &ends_with_e, false).unwrap();
+        assert_eq!(
+            result.as_ref(),
+            &Int32Array::from_slice([0, 3]) as &dyn Array
+        );
+
+        let result = str_unique(&array).unwrap();
+        assert_eq!(
+            strings(result),
+            expect(&[Some("apple"), None, Some("pear")])
+        );
+    }
+}
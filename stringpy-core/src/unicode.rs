@@ -0,0 +1,488 @@
+//! Units strings are measured and sliced in: bytes, code points or user-perceived characters
+//! (extended grapheme clusters, as defined by UAX #29), and the width strings take up when
+//! shown in a monospace font. Also normalization forms, as defined by UAX #15, and case
+//! mapping following the rules of a language, by word and sentence for title and sentence case.
+
+use crate::error::{Error, Result};
+use icu_casemap::titlecase::{LeadingAdjustment, TitlecaseOptions, TrailingCase};
+use icu_casemap::CaseMapper;
+use icu_locid::LanguageIdentifier;
+use icu_segmenter::{GraphemeClusterSegmenter, SentenceSegmenter, WordSegmenter};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use unicode_normalization::{is_nfc_quick, is_nfd_quick, is_nfkc_quick, is_nfkd_quick};
+use unicode_normalization::{IsNormalized, UnicodeNormalization};
+use unicode_width::UnicodeWidthStr;
+
+/// A period with the closing punctuation and spaces after it, where a sentence may end
+const PERIOD: &str = r#"\.[\p{Pe}\p{Pf}"']*\s+"#;
+
+/// Common English abbreviations ending with a period that do not end a sentence, compared
+/// ignoring case
+const ENGLISH_ABBREVIATIONS: [&str; 10] = [
+    "mr", "mrs", "ms", "dr", "prof", "st", "jr", "sr", "vs", "fig",
+];
+
+fn period() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(PERIOD).unwrap())
+}
+
+thread_local! {
+    static GRAPHEMES: GraphemeClusterSegmenter = GraphemeClusterSegmenter::new();
+}
+
+/// Byte offset of the start of each user-perceived character of `x`
+fn grapheme_starts(x: &str) -> Vec<usize> {
+    GRAPHEMES.with(|segmenter| {
+        // breaks include the end of the string, which starts no character
+        segmenter.segment_str(x).filter(|&i| i < x.len()).collect()
+    })
+}
+
+/// The user-perceived characters of `x`, with their byte offset
+fn grapheme_indices(x: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut bounds = grapheme_starts(x);
+    bounds.push(x.len());
+    (1..bounds.len()).map(move |i| (bounds[i - 1], &x[bounds[i - 1]..bounds[i]]))
+}
+
+/// The user-perceived characters of `x`
+pub fn graphemes(x: &str) -> impl Iterator<Item = &str> {
+    grapheme_indices(x).map(|(_, g)| g)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Bytes,
+    Codepoints,
+    Graphemes,
+}
+
+impl Unit {
+    pub fn parse(unit: &str) -> Result<Self> {
+        match unit {
+            "bytes" => Ok(Unit::Bytes),
+            "codepoints" => Ok(Unit::Codepoints),
+            "graphemes" => Ok(Unit::Graphemes),
+            _ => Err(Error::new_value_err(
+                "unit must be one of 'bytes', 'codepoints', 'graphemes'",
+            )),
+        }
+    }
+}
+
+/// A string split in units
+pub struct Units<'a> {
+    x: &'a str,
+    unit: Unit,
+    // byte offset of each unit, unused for bytes
+    starts: Vec<usize>,
+}
+
+impl<'a> Units<'a> {
+    pub fn new(x: &'a str, unit: Unit) -> Self {
+        let starts = match unit {
+            Unit::Bytes => vec![],
+            Unit::Codepoints => x.char_indices().map(|(i, _)| i).collect(),
+            Unit::Graphemes => grapheme_starts(x),
+        };
+        Units { x, unit, starts }
+    }
+
+    /// Number of units
+    pub fn len(&self) -> usize {
+        match self.unit {
+            Unit::Bytes => self.x.len(),
+            _ => self.starts.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Byte offset of unit `n`, the end of the string past the last unit.
+    /// In bytes, an offset inside a character moves back to the start of the character.
+    pub fn offset(&self, n: usize) -> usize {
+        match self.unit {
+            Unit::Bytes => {
+                let mut n = n.min(self.x.len());
+                while !self.x.is_char_boundary(n) {
+                    n -= 1;
+                }
+                n
+            }
+            _ => self.starts.get(n).copied().unwrap_or(self.x.len()),
+        }
+    }
+
+    /// Units `start` to `end`, excluded
+    pub fn slice(&self, start: usize, end: usize) -> &'a str {
+        &self.x[self.offset(start)..self.offset(end)]
+    }
+}
+
+/// Number of units of `x`
+pub fn length(x: &str, unit: Unit) -> usize {
+    match unit {
+        Unit::Bytes => x.len(),
+        Unit::Codepoints => x.chars().count(),
+        Unit::Graphemes => graphemes(x).count(),
+    }
+}
+
+/// How the width given to padding and truncation is measured
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WidthMode {
+    /// Number of units
+    Length,
+    /// Columns in a monospace font, see [`width`]
+    Display,
+}
+
+impl WidthMode {
+    pub fn parse(mode: &str) -> Result<Self> {
+        match mode {
+            "length" => Ok(WidthMode::Length),
+            "display" => Ok(WidthMode::Display),
+            _ => Err(Error::new_value_err(
+                "width_mode must be one of 'length', 'display'",
+            )),
+        }
+    }
+}
+
+/// Columns taken by a single grapheme cluster, as measured by `unicode-width`: emoji sequences
+/// take two columns and the characters joined to the first one add none. Controls take none.
+fn grapheme_width(g: &str) -> usize {
+    if g.starts_with(char::is_control) {
+        0
+    } else {
+        g.width()
+    }
+}
+
+/// Columns `x` takes up in a monospace font. Wide East Asian characters and emoji take two
+/// columns, combining marks take none as they are drawn over the character before them.
+pub fn width(x: &str) -> usize {
+    if x.is_ascii() {
+        return x.bytes().filter(|b| !b.is_ascii_control()).count();
+    }
+    graphemes(x).map(grapheme_width).sum()
+}
+
+/// Longest start of `x` at most `width` columns wide
+pub fn prefix(x: &str, width: usize) -> &str {
+    let mut columns = 0;
+    for (start, g) in grapheme_indices(x) {
+        columns += grapheme_width(g);
+        if columns > width {
+            return &x[..start];
+        }
+    }
+    x
+}
+
+/// Longest end of `x` at most `width` columns wide
+pub fn suffix(x: &str, width: usize) -> &str {
+    let graphemes: Vec<_> = grapheme_indices(x).collect();
+    let mut columns = 0;
+    for (start, g) in graphemes.into_iter().rev() {
+        columns += grapheme_width(g);
+        if columns > width {
+            return &x[start + g.len()..];
+        }
+    }
+    x
+}
+
+/// Unicode normalization form
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Form {
+    /// Canonical composition, e.g. `e` followed by a combining acute accent becomes `é`
+    Nfc,
+    /// Canonical decomposition, `é` becomes `e` followed by a combining acute accent
+    Nfd,
+    /// Compatibility composition, also folds variants such as `ﬁ` or `①` to `fi` and `1`
+    Nfkc,
+    /// Compatibility decomposition
+    Nfkd,
+}
+
+impl Form {
+    pub fn parse(form: &str) -> Result<Self> {
+        match form {
+            "NFC" => Ok(Form::Nfc),
+            "NFD" => Ok(Form::Nfd),
+            "NFKC" => Ok(Form::Nfkc),
+            "NFKD" => Ok(Form::Nfkd),
+            _ => Err(Error::new_value_err(
+                "form must be one of 'NFC', 'NFD', 'NFKC', 'NFKD'",
+            )),
+        }
+    }
+}
+
+/// `x` in normalization form `form`, borrowed when it already is
+pub fn normalize(x: &str, form: Form) -> Cow<'_, str> {
+    let normalized = match form {
+        Form::Nfc => is_nfc_quick(x.chars()),
+        Form::Nfd => is_nfd_quick(x.chars()),
+        Form::Nfkc => is_nfkc_quick(x.chars()),
+        Form::Nfkd => is_nfkd_quick(x.chars()),
+    };
+    if normalized == IsNormalized::Yes {
+        return Cow::Borrowed(x);
+    }
+    Cow::Owned(match form {
+        Form::Nfc => x.nfc().collect(),
+        Form::Nfd => x.nfd().collect(),
+        Form::Nfkc => x.nfkc().collect(),
+        Form::Nfkd => x.nfkd().collect(),
+    })
+}
+
+/// Diacritics of the combining diacritical marks blocks, which precomposed letters of the
+/// Latin, Greek and Cyrillic scripts decompose to. Marks of other scripts, e.g. vowel signs,
+/// are part of the letter and not matched.
+fn is_accent(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}')
+}
+
+/// Letters with a stroke have no decomposition, the stroke is removed as an accent
+fn remove_stroke(c: char) -> char {
+    match c {
+        'Đ' => 'D',
+        'đ' => 'd',
+        'Ħ' => 'H',
+        'ħ' => 'h',
+        'Ł' => 'L',
+        'ł' => 'l',
+        'Ø' => 'O',
+        'ø' => 'o',
+        'Ŧ' => 'T',
+        'ŧ' => 't',
+        _ => c,
+    }
+}
+
+/// `x` without accents, base letters are kept in their own script: `Crème brûlée` becomes
+/// `Creme brulee` and `Ёлка` becomes `Елка`
+pub fn remove_accents(x: &str) -> Cow<'_, str> {
+    if x.is_ascii() {
+        return Cow::Borrowed(x);
+    }
+    Cow::Owned(
+        x.nfd()
+            .filter(|c| !is_accent(*c))
+            .map(remove_stroke)
+            .nfc()
+            .collect(),
+    )
+}
+
+// not `Sync`, but built from static data at no cost
+const CASE_MAPPER: CaseMapper = CaseMapper::new();
+
+thread_local! {
+    // UAX #29 word boundaries, not `Sync` either
+    static WORDS: WordSegmenter = WordSegmenter::new_auto();
+    static SENTENCES: SentenceSegmenter = SentenceSegmenter::new();
+}
+
+/// Language whose case mapping rules apply, e.g. the dotted and dotless i of Turkish.
+/// The default is the root locale, with the language independent rules.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Locale(LanguageIdentifier);
+
+impl Locale {
+    /// Parse a language tag such as `tr` or `lt-LT`
+    pub fn parse(locale: &str) -> Result<Self> {
+        locale
+            .parse()
+            .map(Locale)
+            .map_err(|_| Error::new_value_err(format!("Invalid locale '{}'", locale)))
+    }
+
+    /// Turkish and Azeri, which fold `I` to the dotless `ı`
+    fn is_turkic(&self) -> bool {
+        matches!(self.0.language.as_str(), "tr" | "az")
+    }
+
+    /// Abbreviations of the language that do not end a sentence, none when it has no list
+    fn abbreviations(&self) -> &'static [&'static str] {
+        match self.0.language.as_str() {
+            "en" => &ENGLISH_ABBREVIATIONS,
+            _ => &[],
+        }
+    }
+}
+
+/// `x` itself when mapping it left it unchanged
+fn borrow_unchanged(x: &str, mapped: String) -> Cow<'_, str> {
+    if mapped == x {
+        Cow::Borrowed(x)
+    } else {
+        Cow::Owned(mapped)
+    }
+}
+
+/// Full uppercase mapping of `x`, e.g. `ß` becomes `SS`
+pub fn to_upper<'a>(x: &'a str, locale: &Locale) -> Cow<'a, str> {
+    // ASCII with no lowercase letter maps to itself in every language
+    if x.bytes().all(|b| b.is_ascii() && !b.is_ascii_lowercase()) {
+        return Cow::Borrowed(x);
+    }
+    borrow_unchanged(x, CASE_MAPPER.uppercase_to_string(x, &locale.0))
+}
+
+/// Full lowercase mapping of `x`
+pub fn to_lower<'a>(x: &'a str, locale: &Locale) -> Cow<'a, str> {
+    // ASCII with no uppercase letter maps to itself in every language
+    if x.bytes().all(|b| b.is_ascii() && !b.is_ascii_uppercase()) {
+        return Cow::Borrowed(x);
+    }
+    borrow_unchanged(x, CASE_MAPPER.lowercase_to_string(x, &locale.0))
+}
+
+/// Full case folding of `x`, for caseless comparison rather than display:
+/// `Straße` and `STRASSE` both fold to `strasse`
+pub fn casefold<'a>(x: &'a str, locale: &Locale) -> Cow<'a, str> {
+    let folded = if locale.is_turkic() {
+        CASE_MAPPER.fold_turkic_string(x)
+    } else {
+        CASE_MAPPER.fold_string(x)
+    };
+    borrow_unchanged(x, folded)
+}
+
+/// Whether `x` and `y` are equal ignoring case, compared after canonical decomposition so
+/// that precomposed and combining accents match
+pub fn caseless_eq(x: &str, y: &str, locale: &Locale) -> bool {
+    if x.is_ascii() && y.is_ascii() && !locale.is_turkic() {
+        return x.eq_ignore_ascii_case(y);
+    }
+    let fold = |x: &str| -> String {
+        // Turkic folding maps İ to i, but decomposing first would turn it into I + U+0307,
+        // and I then folds to ı: compose, fold, and only decompose afterwards
+        let x = if locale.is_turkic() {
+            normalize(x, Form::Nfc)
+        } else {
+            normalize(x, Form::Nfd)
+        };
+        normalize(&casefold(&x, locale), Form::Nfd).into_owned()
+    };
+    fold(x) == fold(y)
+}
+
+/// Title case the first character of `x`, or its first cased letter with `skip_uncased`, and
+/// lowercase the rest of it with `lower_rest`
+fn titlecase(x: &str, locale: &Locale, lower_rest: bool, skip_uncased: bool) -> String {
+    let mut options = TitlecaseOptions::default();
+    options.leading_adjustment = if skip_uncased {
+        LeadingAdjustment::ToCased
+    } else {
+        LeadingAdjustment::None
+    };
+    options.trailing_case = if lower_rest {
+        TrailingCase::Lower
+    } else {
+        TrailingCase::Unchanged
+    };
+    CASE_MAPPER.titlecase_segment_with_only_case_data_to_string(x, &locale.0, options)
+}
+
+/// Words kept in lower case by [`to_title`], compared case folded
+pub fn lower_words(words: &[&str], locale: &Locale) -> HashSet<String> {
+    words
+        .iter()
+        .map(|x| casefold(x, locale).into_owned())
+        .collect()
+}
+
+/// Title case each word of `x`, but for the `keep_lower` ones after the first word
+pub fn to_title(
+    x: &str,
+    locale: &Locale,
+    lower_rest: bool,
+    keep_lower: &HashSet<String>,
+) -> String {
+    WORDS.with(|words| {
+        let mut result = String::with_capacity(x.len());
+        let mut first = true;
+        let mut last = 0;
+        for (end, word_type) in words.segment_str(x).iter_with_word_type() {
+            let word = &x[last..end];
+            last = end;
+            if !word_type.is_word_like() {
+                result.push_str(word);
+            } else if !first && keep_lower.contains(casefold(word, locale).as_ref()) {
+                result.push_str(&to_lower(word, locale));
+            } else {
+                result.push_str(&titlecase(word, locale, lower_rest, false));
+                first = false;
+            }
+        }
+        result
+    })
+}
+
+/// Whether `word`, followed by a period, is an abbreviation rather than the end of a sentence:
+/// one with periods inside such as `e.g`, or a common one of the language
+fn is_abbreviation(word: &str, locale: &Locale) -> bool {
+    word.contains('.')
+        || locale
+            .abbreviations()
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(word))
+}
+
+/// Byte offsets where the sentences of `x` start, but for the first one
+fn sentence_starts(x: &str, locale: &Locale) -> Vec<usize> {
+    let mut starts: Vec<usize> = SENTENCES.with(|sentences| {
+        sentences
+            .segment_str(x)
+            .filter(|&i| i > 0 && i < x.len())
+            .collect()
+    });
+    // UAX #29 never splits before a lowercase letter (rule SB8), so text that is not cased yet
+    // would stay one sentence. Also split after a period followed by lowercase text, unless the
+    // word before it is an abbreviation. The boundaries of UAX #29 are all kept.
+    for m in period().find_iter(x) {
+        // the next sentence may start with quotes or brackets
+        let next = x[m.end()..].trim_start_matches(|c: char| !c.is_alphanumeric());
+        if !next.starts_with(char::is_lowercase) {
+            continue;
+        }
+        let word = x[..m.start()]
+            .rsplit(|c: char| !(c.is_alphanumeric() || c == '.'))
+            .next()
+            .unwrap_or_default();
+        if !is_abbreviation(word, locale) {
+            starts.push(m.end());
+        }
+    }
+    starts.sort_unstable();
+    starts.dedup();
+    starts
+}
+
+/// Capitalize the first letter of each sentence of `x`
+pub fn to_sentence(x: &str, locale: &Locale, lower_rest: bool) -> String {
+    let mut result = String::with_capacity(x.len());
+    let mut last = 0;
+    for end in sentence_starts(x, locale).into_iter().chain([x.len()]) {
+        // sentences may start with quotes or spaces
+        result.push_str(&titlecase(&x[last..end], locale, lower_rest, true));
+        last = end;
+    }
+    result
+}
@@ -0,0 +1,126 @@
+use crate::error::Result;
+use arrow2::array::{Array, DictionaryArray};
+use arrow2::compute::take::take;
+use arrow2::datatypes::{DataType, IntegerType};
+
+/// Downcast `$array` to a string array and evaluate `$body` with `$arr` bound to it.
+/// Inside `$body`, `$O` is the offset type of the input (`i32` for `Utf8`, `i64` for `LargeUtf8`),
+/// so the output can be built with the same offset width as the input.
+#[macro_export]
+macro_rules! with_utf8 {
+    ($array:expr; $O:ident, $arr:ident => $body:expr) => {
+        match $array.data_type() {
+            $crate::arrow2::datatypes::DataType::Utf8 => {
+                #[allow(dead_code)]
+                type $O = i32;
+                let $arr = $array
+                    .as_any()
+                    .downcast_ref::<$crate::arrow2::array::Utf8Array<i32>>()
+                    .unwrap();
+                $body
+            }
+            $crate::arrow2::datatypes::DataType::LargeUtf8 => {
+                #[allow(dead_code)]
+                type $O = i64;
+                let $arr = $array
+                    .as_any()
+                    .downcast_ref::<$crate::arrow2::array::Utf8Array<i64>>()
+                    .unwrap();
+                $body
+            }
+            _ => Err($crate::error::Error::new_value_err("Expect string array").into()),
+        }
+    };
+}
+
+/// Iterate over a string array regardless of its offset width
+pub fn utf8_iter(array: &dyn Array) -> Result<Box<dyn Iterator<Item = Option<&str>> + '_>> {
+    with_utf8!(array; _O, array => Ok(Box::new(array.iter())))
+}
+
+/// Iterate over a string array split into chunks, chunk after chunk
+pub fn utf8_chunks_iter(chunks: &[Box<dyn Array>]) -> Result<impl Iterator<Item = Option<&str>>> {
+    let chunks = chunks
+        .iter()
+        .map(|chunk| utf8_iter(chunk.as_ref()))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(chunks.into_iter().flatten())
+}
+
+/// Decode the dictionary-encoded chunks with [`decode_dictionary`], other chunks are kept as is
+pub fn decode_chunks(chunks: &[Box<dyn Array>]) -> Result<Vec<Box<dyn Array>>> {
+    chunks
+        .iter()
+        .map(|chunk| Ok(decode_dictionary(chunk.as_ref())?.unwrap_or_else(|| chunk.clone())))
+        .collect()
+}
+
+/// Run `kernel` on `array`. A dictionary-encoded input is handled by running the kernel
+/// once per dictionary value instead of once per row: string outputs reuse the keys and are
+/// returned dictionary-encoded, other outputs are gathered back to one value per row.
+pub fn apply_dictionary<F>(array: &dyn Array, kernel: F) -> Result<Box<dyn Array>>
+where
+    F: Fn(&dyn Array) -> Result<Box<dyn Array>>,
+{
+    macro_rules! remap {
+        ($K:ty, $key_type:expr, $is_ordered:expr) => {{
+            let array = array
+                .as_any()
+                .downcast_ref::<DictionaryArray<$K>>()
+                .unwrap();
+            let values = kernel(array.values().as_ref())?;
+            match values.data_type() {
+                DataType::Utf8 | DataType::LargeUtf8 => {
+                    let data_type = DataType::Dictionary(
+                        $key_type,
+                        Box::new(values.data_type().clone()),
+                        $is_ordered,
+                    );
+                    Ok(DictionaryArray::try_new(data_type, array.keys().clone(), values)?.boxed())
+                }
+                _ => Ok(take(values.as_ref(), array.keys())?),
+            }
+        }};
+    }
+
+    match array.data_type() {
+        DataType::Dictionary(key_type, _, is_ordered) => match key_type {
+            IntegerType::Int8 => remap!(i8, *key_type, *is_ordered),
+            IntegerType::Int16 => remap!(i16, *key_type, *is_ordered),
+            IntegerType::Int32 => remap!(i32, *key_type, *is_ordered),
+            IntegerType::Int64 => remap!(i64, *key_type, *is_ordered),
+            IntegerType::UInt8 => remap!(u8, *key_type, *is_ordered),
+            IntegerType::UInt16 => remap!(u16, *key_type, *is_ordered),
+            IntegerType::UInt32 => remap!(u32, *key_type, *is_ordered),
+            IntegerType::UInt64 => remap!(u64, *key_type, *is_ordered),
+        },
+        _ => kernel(array),
+    }
+}
+
+/// Decode a dictionary-encoded array back to one value per row, `None` if `array` is not dictionary-encoded
+pub fn decode_dictionary(array: &dyn Array) -> Result<Option<Box<dyn Array>>> {
+    macro_rules! decode {
+        ($K:ty) => {{
+            let array = array
+                .as_any()
+                .downcast_ref::<DictionaryArray<$K>>()
+                .unwrap();
+            Ok(Some(take(array.values().as_ref(), array.keys())?))
+        }};
+    }
+
+    match array.data_type() {
+        DataType::Dictionary(key_type, _, _) => match key_type {
+            IntegerType::Int8 => decode!(i8),
+            IntegerType::Int16 => decode!(i16),
+            IntegerType::Int32 => decode!(i32),
+            IntegerType::Int64 => decode!(i64),
+            IntegerType::UInt8 => decode!(u8),
+            IntegerType::UInt16 => decode!(u16),
+            IntegerType::UInt32 => decode!(u32),
+            IntegerType::UInt64 => decode!(u64),
+        },
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,103 @@
+//! The outputs of the parallel tasks are concatenated without holding a second full copy of
+//! the output. A counting allocator records the peak of live bytes while a kernel runs.
+
+use arrow2::array::{Array, BooleanArray, Int32Array, Utf8Array};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use stringpy_core::kernel::{map, map_rows, Boolean, Int32, Utf8};
+
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn grow(size: usize) {
+    let live = LIVE.fetch_add(size, Ordering::SeqCst) + size;
+    PEAK.fetch_max(live, Ordering::SeqCst);
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        grow(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+
+    // a realloc may copy the block, the old and the new one are both live until it returns
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        grow(new_size);
+        let ptr = System.realloc(ptr, layout, new_size);
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+        ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Run `f` and return its result with the peak of bytes allocated while it ran, above what was
+/// live before, and the bytes still live after it, i.e. held by the result
+fn measure<T>(f: impl FnOnce() -> T) -> (T, usize, usize) {
+    let before = LIVE.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let result = f();
+    let peak = PEAK.load(Ordering::SeqCst) - before;
+    let after = LIVE.load(Ordering::SeqCst) - before;
+    (result, peak, after)
+}
+
+fn assert_peak(peak: usize, output: usize) {
+    // the output, plus one wave of tasks in flight and the task bookkeeping
+    assert!(
+        peak < output + output / 2,
+        "peak of {} bytes for an output of {} bytes",
+        peak,
+        output
+    );
+}
+
+#[test]
+fn concatenating_tasks_does_not_double_peak_memory() {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(8)
+        .build()
+        .unwrap();
+    // rows per task that are not a multiple of 8, so that the validity of the tasks is not
+    // byte aligned
+    let array = Utf8Array::<i32>::from_iter(
+        (0..1_000_003).map(|i| (i % 7 != 0).then(|| format!("row {:08}", i))),
+    );
+
+    pool.install(|| {
+        let (result, peak, output) =
+            measure(|| map(&array, &Utf8, |x| Ok(Some(Cow::Owned(x.to_uppercase())))).unwrap());
+        let strings = result.as_any().downcast_ref::<Utf8Array<i32>>().unwrap();
+        assert_eq!(strings.value(125_002), "ROW 00125002");
+        let expect: Utf8Array<i32> = array.iter().map(|x| Some(x?.to_uppercase())).collect();
+        assert_eq!(strings, &expect);
+        assert_peak(peak, output);
+        drop(result);
+
+        let (result, peak, output) = measure(|| {
+            map_rows(&array, &Int32::new(), |x, i| Ok(Some((x.len() + i) as i32))).unwrap()
+        });
+        let integers = result.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(integers.value(1), 13);
+        assert!(integers.is_null(7));
+        assert_peak(peak, output);
+        drop(result);
+
+        // booleans are bitmaps, the tasks are not byte aligned
+        let (result, peak, output) =
+            measure(|| map(&array, &Boolean, |x| Ok(Some(x.ends_with('1')))).unwrap());
+        let booleans = result.as_any().downcast_ref::<BooleanArray>().unwrap();
+        let expect: BooleanArray = array.iter().map(|x| Some(x?.ends_with('1'))).collect();
+        assert_eq!(booleans, &expect);
+        assert_peak(peak, output);
+    });
+}
@@ -0,0 +1,7 @@
+'''Polars expression plugin over the string kernels of stringpy, registered on expressions by
+:mod:`stringpy.polars`.'''
+
+from pathlib import Path
+
+# directory of the compiled plugin, as expected by ``polars.plugins.register_plugin_function``
+PLUGIN_PATH = Path(__file__).parent
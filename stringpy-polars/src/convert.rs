@@ -0,0 +1,43 @@
+//! Move arrays between polars and the `arrow2` arrays of `stringpy_core` through the Arrow C
+//! data interface. Both sides own their buffers, only the `ArrowArray` and `ArrowSchema`
+//! structs are handed over.
+use polars::prelude::*;
+use polars_arrow::datatypes::Field as ArrowField;
+use polars_arrow::ffi as polars_ffi;
+
+fn arrow2_err(err: arrow2::error::Error) -> PolarsError {
+    polars_err!(ComputeError: "{}", err)
+}
+
+/// The strings of `series` as one `arrow2` array. Polars stores strings as views, which
+/// `arrow2` can't import, so they are converted to `LargeUtf8` first.
+pub fn to_arrow2(series: &Series) -> PolarsResult<Box<dyn arrow2::array::Array>> {
+    series.str()?;
+    let array = series.rechunk().to_arrow(0, CompatLevel::oldest());
+    let field = ArrowField::new(PlSmallStr::EMPTY, array.dtype().clone(), true);
+    let schema = polars_ffi::export_field_to_c(&field);
+    let array = polars_ffi::export_array_to_c(array);
+    // SAFETY: both crates define the structs of the C data interface with the same `repr(C)`
+    // layout, and the release callbacks travel with them
+    unsafe {
+        let schema: arrow2::ffi::ArrowSchema = std::mem::transmute(schema);
+        let array: arrow2::ffi::ArrowArray = std::mem::transmute(array);
+        let field = arrow2::ffi::import_field_from_c(&schema).map_err(arrow2_err)?;
+        arrow2::ffi::import_array_from_c(array, field.data_type).map_err(arrow2_err)
+    }
+}
+
+/// A series named `name` holding the result of a kernel
+pub fn from_arrow2(name: PlSmallStr, array: Box<dyn arrow2::array::Array>) -> PolarsResult<Series> {
+    let field = arrow2::datatypes::Field::new("", array.data_type().clone(), true);
+    let schema = arrow2::ffi::export_field_to_c(&field);
+    let array = arrow2::ffi::export_array_to_c(array);
+    // SAFETY: as in `to_arrow2`
+    let array = unsafe {
+        let schema: polars_ffi::ArrowSchema = std::mem::transmute(schema);
+        let array: polars_ffi::ArrowArray = std::mem::transmute(array);
+        let field = polars_ffi::import_field_from_c(&schema)?;
+        polars_ffi::import_array_from_c(array, field.dtype)?
+    };
+    Series::from_arrow(name, array)
+}
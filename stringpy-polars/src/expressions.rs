@@ -0,0 +1,518 @@
+//! The `str_*` kernels of `stringpy_core` as polars expressions. The first input is the string
+//! column, per-row arguments follow it as columns of one value per row or a single value for
+//! all rows, and the other arguments are keyword arguments.
+use crate::convert;
+use arrow2::array::Array;
+use polars::prelude::*;
+use pyo3_polars::derive::polars_expr;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use stringpy_core::pattern::{self, Anchor, Matcher, MatcherSet, Options};
+use stringpy_core::str as kernels;
+use stringpy_core::unicode::{Form, Locale, Unit, WidthMode};
+
+fn core_err(err: stringpy_core::Error) -> PolarsError {
+    match err {
+        stringpy_core::Error::ValueErr(message) => polars_err!(InvalidOperation: "{}", message),
+        err => polars_err!(ComputeError: "{}", err),
+    }
+}
+
+/// Run `kernel` on the strings of `series`, the result keeps its name
+fn apply<F>(series: &Series, kernel: F) -> PolarsResult<Series>
+where
+    F: FnOnce(&dyn Array) -> stringpy_core::Result<Box<dyn Array>>,
+{
+    let array = convert::to_arrow2(series)?;
+    let result = kernel(array.as_ref()).map_err(core_err)?;
+    convert::from_arrow2(series.name().clone(), result)
+}
+
+/// Values of the per-row string argument `name`
+fn strings<'a>(series: &'a Series, name: &str) -> PolarsResult<Vec<&'a str>> {
+    series
+        .str()?
+        .into_iter()
+        .map(|x| x.ok_or_else(|| polars_err!(InvalidOperation: "{} must not be null", name)))
+        .collect()
+}
+
+/// Values of the per-row integer argument `name`, converted to `T`
+fn integers<T: TryFrom<i64>>(series: &Series, name: &str) -> PolarsResult<Vec<T>> {
+    let series = series.cast(&DataType::Int64)?;
+    series
+        .i64()?
+        .into_iter()
+        .map(|x| {
+            let x = x.ok_or_else(|| polars_err!(InvalidOperation: "{} must not be null", name))?;
+            T::try_from(x)
+                .map_err(|_| polars_err!(InvalidOperation: "{} is out of range: {}", name, x))
+        })
+        .collect()
+}
+
+/// Values of the per-row argument `name` holding single characters
+fn chars(series: &Series, name: &str) -> PolarsResult<Vec<char>> {
+    strings(series, name)?
+        .into_iter()
+        .map(|x| {
+            let mut chars = x.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(polars_err!(InvalidOperation: "{} must be a single character", name)),
+            }
+        })
+        .collect()
+}
+
+/// The options of a pattern, as the attributes of a Python `Pattern`
+#[derive(Deserialize)]
+struct Flags {
+    fixed: bool,
+    ignore_case: bool,
+    multiline: bool,
+    dotall: bool,
+    comments: bool,
+    unicode: bool,
+}
+
+impl From<&Flags> for Options {
+    fn from(flags: &Flags) -> Self {
+        Options {
+            fixed: flags.fixed,
+            ignore_case: flags.ignore_case,
+            multiline: flags.multiline,
+            dotall: flags.dotall,
+            comments: flags.comments,
+            unicode: flags.unicode,
+        }
+    }
+}
+
+/// Compile per-row patterns sharing `flags`, each distinct one once. With an `anchor`, the
+/// patterns only match there, see [`pattern::anchored`].
+fn compile(
+    sources: &[&str],
+    flags: &Flags,
+    anchor: Option<Anchor>,
+) -> PolarsResult<Vec<Arc<Matcher>>> {
+    let mut compiled: HashMap<&str, Arc<Matcher>> = HashMap::new();
+    sources
+        .iter()
+        .map(|&source| {
+            if let Some(matcher) = compiled.get(source) {
+                return Ok(matcher.clone());
+            }
+            let (pattern, options) = match anchor {
+                Some(anchor) => pattern::anchored(source, flags.into(), anchor),
+                None => (source.into(), flags.into()),
+            };
+            let matcher = Arc::new(Matcher::new(&pattern, options).map_err(core_err)?);
+            compiled.insert(source, matcher.clone());
+            Ok(matcher)
+        })
+        .collect()
+}
+
+/// Compile the per-row patterns of the second input
+fn compile_input(
+    inputs: &[Series],
+    flags: &Flags,
+    anchor: Option<Anchor>,
+) -> PolarsResult<Vec<Arc<Matcher>>> {
+    compile(&strings(&inputs[1], "pattern")?, flags, anchor)
+}
+
+/// The root locale when none is given
+fn parse_locale(locale: Option<&str>) -> PolarsResult<Locale> {
+    let locale = locale.map(Locale::parse).transpose().map_err(core_err)?;
+    Ok(locale.unwrap_or_default())
+}
+
+fn list_of(input_fields: &[Field], dtype: DataType) -> PolarsResult<Field> {
+    Ok(Field::new(
+        input_fields[0].name().clone(),
+        DataType::List(Box::new(dtype)),
+    ))
+}
+
+fn list_string(input_fields: &[Field]) -> PolarsResult<Field> {
+    list_of(input_fields, DataType::String)
+}
+
+fn list_int32(input_fields: &[Field]) -> PolarsResult<Field> {
+    list_of(input_fields, DataType::Int32)
+}
+
+#[derive(Deserialize)]
+struct PatternKwargs {
+    options: Flags,
+}
+
+#[derive(Deserialize)]
+struct AnchoredKwargs {
+    options: Flags,
+    negate: bool,
+}
+
+#[derive(Deserialize)]
+struct GroupKwargs {
+    options: Flags,
+    group: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct PatternSetKwargs {
+    patterns: Vec<String>,
+    options: Flags,
+}
+
+#[derive(Deserialize)]
+struct CaseWhenKwargs {
+    patterns: Vec<String>,
+    labels: Vec<String>,
+    default: Option<String>,
+    options: Flags,
+}
+
+#[derive(Deserialize)]
+struct OnePatternKwargs {
+    pattern: String,
+    options: Flags,
+}
+
+#[derive(Deserialize)]
+struct SubsetKwargs {
+    pattern: String,
+    options: Flags,
+    negate: bool,
+}
+
+#[derive(Deserialize)]
+struct SideKwargs {
+    side: String,
+}
+
+#[derive(Deserialize)]
+struct FormKwargs {
+    form: String,
+}
+
+#[derive(Deserialize)]
+struct UnitKwargs {
+    unit: String,
+}
+
+#[derive(Deserialize)]
+struct TruncKwargs {
+    width: usize,
+    side: String,
+    ellipsis: String,
+    unit: String,
+    width_mode: String,
+}
+
+#[derive(Deserialize)]
+struct PadKwargs {
+    unit: String,
+    width_mode: String,
+}
+
+#[derive(Deserialize)]
+struct LocaleKwargs {
+    locale: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TitleKwargs {
+    locale: Option<String>,
+    lower_rest: bool,
+    keep_lower: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct SentenceKwargs {
+    locale: Option<String>,
+    lower_rest: bool,
+}
+
+#[derive(Deserialize)]
+struct EqualKwargs {
+    ignore_case: bool,
+    locale: Option<String>,
+}
+
+fn set_of(patterns: &[String], flags: &Flags) -> PolarsResult<MatcherSet> {
+    let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+    MatcherSet::new(&patterns, flags.into()).map_err(core_err)
+}
+
+#[polars_expr(output_type=Int32)]
+fn str_count(inputs: &[Series], kwargs: PatternKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| kernels::str_count(array, &pattern))
+}
+
+#[polars_expr(output_type=String)]
+fn str_replace(inputs: &[Series], kwargs: PatternKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    let replace = strings(&inputs[2], "replace")?;
+    apply(&inputs[0], |array| {
+        kernels::str_replace(array, &pattern, &replace)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_replace_all(inputs: &[Series], kwargs: PatternKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    let replace = strings(&inputs[2], "replace")?;
+    apply(&inputs[0], |array| {
+        kernels::str_replace_all(array, &pattern, &replace)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_remove(inputs: &[Series], kwargs: PatternKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| kernels::str_remove(array, &pattern))
+}
+
+#[polars_expr(output_type=String)]
+fn str_remove_all(inputs: &[Series], kwargs: PatternKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| kernels::str_remove_all(array, &pattern))
+}
+
+#[polars_expr(output_type=String)]
+fn str_squish(inputs: &[Series]) -> PolarsResult<Series> {
+    apply(&inputs[0], kernels::str_squish)
+}
+
+#[polars_expr(output_type=String)]
+fn str_trim(inputs: &[Series], kwargs: SideKwargs) -> PolarsResult<Series> {
+    apply(&inputs[0], |array| kernels::str_trim(array, &kwargs.side))
+}
+
+#[polars_expr(output_type=Boolean)]
+fn str_detect(inputs: &[Series], kwargs: PatternKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| kernels::str_detect(array, &pattern))
+}
+
+#[polars_expr(output_type=Boolean)]
+fn str_detect_any(inputs: &[Series], kwargs: PatternSetKwargs) -> PolarsResult<Series> {
+    let patterns = set_of(&kwargs.patterns, &kwargs.options)?;
+    apply(&inputs[0], |array| {
+        kernels::str_detect_any(array, &patterns)
+    })
+}
+
+#[polars_expr(output_type_func=list_int32)]
+fn str_which_patterns(inputs: &[Series], kwargs: PatternSetKwargs) -> PolarsResult<Series> {
+    let patterns = set_of(&kwargs.patterns, &kwargs.options)?;
+    apply(&inputs[0], |array| {
+        kernels::str_which_patterns(array, &patterns)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_case_when(inputs: &[Series], kwargs: CaseWhenKwargs) -> PolarsResult<Series> {
+    let patterns = set_of(&kwargs.patterns, &kwargs.options)?;
+    let labels: Vec<&str> = kwargs.labels.iter().map(String::as_str).collect();
+    apply(&inputs[0], |array| {
+        kernels::str_case_when(array, &patterns, &labels, kwargs.default.as_deref())
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_remove_ascent(inputs: &[Series]) -> PolarsResult<Series> {
+    apply(&inputs[0], kernels::str_remove_ascent)
+}
+
+#[polars_expr(output_type=String)]
+fn str_transliterate(inputs: &[Series]) -> PolarsResult<Series> {
+    apply(&inputs[0], kernels::str_transliterate)
+}
+
+#[polars_expr(output_type=String)]
+fn str_normalize(inputs: &[Series], kwargs: FormKwargs) -> PolarsResult<Series> {
+    let form = Form::parse(&kwargs.form).map_err(core_err)?;
+    apply(&inputs[0], |array| kernels::str_normalize(array, form))
+}
+
+#[polars_expr(output_type=String)]
+fn str_trunc(inputs: &[Series], kwargs: TruncKwargs) -> PolarsResult<Series> {
+    let unit = Unit::parse(&kwargs.unit).map_err(core_err)?;
+    let mode = WidthMode::parse(&kwargs.width_mode).map_err(core_err)?;
+    apply(&inputs[0], |array| {
+        kernels::str_trunc(
+            array,
+            kwargs.width,
+            &kwargs.side,
+            &kwargs.ellipsis,
+            unit,
+            mode,
+        )
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_extract(inputs: &[Series], kwargs: GroupKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| {
+        kernels::str_extract(array, &pattern, kwargs.group)
+    })
+}
+
+#[polars_expr(output_type_func=list_string)]
+fn str_extract_all(inputs: &[Series], kwargs: GroupKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| {
+        kernels::str_extract_all(array, &pattern, kwargs.group)
+    })
+}
+
+#[polars_expr(output_type_func=list_string)]
+fn str_split(inputs: &[Series], kwargs: PatternKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, None)?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| {
+        kernels::str_split(array, &pattern, None)
+    })
+}
+
+#[polars_expr(output_type=Boolean)]
+fn str_starts(inputs: &[Series], kwargs: AnchoredKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, Some(Anchor::Start))?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| {
+        kernels::str_starts(array, &pattern, kwargs.negate)
+    })
+}
+
+#[polars_expr(output_type=Boolean)]
+fn str_ends(inputs: &[Series], kwargs: AnchoredKwargs) -> PolarsResult<Series> {
+    let pattern = compile_input(inputs, &kwargs.options, Some(Anchor::End))?;
+    let pattern: Vec<&Matcher> = pattern.iter().map(Arc::as_ref).collect();
+    apply(&inputs[0], |array| {
+        kernels::str_ends(array, &pattern, kwargs.negate)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_subset(inputs: &[Series], kwargs: SubsetKwargs) -> PolarsResult<Series> {
+    let pattern = Matcher::new(&kwargs.pattern, (&kwargs.options).into()).map_err(core_err)?;
+    apply(&inputs[0], |array| {
+        kernels::str_subset(array, &pattern, kwargs.negate)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_dup(inputs: &[Series]) -> PolarsResult<Series> {
+    let times = integers(&inputs[1], "times")?;
+    apply(&inputs[0], |array| kernels::str_dup(array, &times))
+}
+
+#[polars_expr(output_type=Int32)]
+fn str_length(inputs: &[Series], kwargs: UnitKwargs) -> PolarsResult<Series> {
+    let unit = Unit::parse(&kwargs.unit).map_err(core_err)?;
+    apply(&inputs[0], |array| kernels::str_length(array, unit))
+}
+
+#[polars_expr(output_type=Int32)]
+fn str_width(inputs: &[Series]) -> PolarsResult<Series> {
+    apply(&inputs[0], kernels::str_width)
+}
+
+#[polars_expr(output_type=String)]
+fn str_unique(inputs: &[Series]) -> PolarsResult<Series> {
+    apply(&inputs[0], kernels::str_unique)
+}
+
+#[polars_expr(output_type=String)]
+fn str_to_upper(inputs: &[Series], kwargs: LocaleKwargs) -> PolarsResult<Series> {
+    let locale = parse_locale(kwargs.locale.as_deref())?;
+    apply(&inputs[0], |array| kernels::str_to_upper(array, &locale))
+}
+
+#[polars_expr(output_type=String)]
+fn str_to_lower(inputs: &[Series], kwargs: LocaleKwargs) -> PolarsResult<Series> {
+    let locale = parse_locale(kwargs.locale.as_deref())?;
+    apply(&inputs[0], |array| kernels::str_to_lower(array, &locale))
+}
+
+#[polars_expr(output_type=String)]
+fn str_to_title(inputs: &[Series], kwargs: TitleKwargs) -> PolarsResult<Series> {
+    let locale = parse_locale(kwargs.locale.as_deref())?;
+    let keep_lower = kwargs.keep_lower.unwrap_or_default();
+    let keep_lower: Vec<&str> = keep_lower.iter().map(String::as_str).collect();
+    apply(&inputs[0], |array| {
+        kernels::str_to_title(array, &locale, kwargs.lower_rest, &keep_lower)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_to_sentence(inputs: &[Series], kwargs: SentenceKwargs) -> PolarsResult<Series> {
+    let locale = parse_locale(kwargs.locale.as_deref())?;
+    apply(&inputs[0], |array| {
+        kernels::str_to_sentence(array, &locale, kwargs.lower_rest)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_casefold(inputs: &[Series], kwargs: LocaleKwargs) -> PolarsResult<Series> {
+    let locale = parse_locale(kwargs.locale.as_deref())?;
+    apply(&inputs[0], |array| kernels::str_casefold(array, &locale))
+}
+
+#[polars_expr(output_type=Boolean)]
+fn str_equal(inputs: &[Series], kwargs: EqualKwargs) -> PolarsResult<Series> {
+    let locale = parse_locale(kwargs.locale.as_deref())?;
+    let other = strings(&inputs[1], "other")?;
+    apply(&inputs[0], |array| {
+        kernels::str_equal(array, &other, kwargs.ignore_case, &locale)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_pad(inputs: &[Series], kwargs: PadKwargs) -> PolarsResult<Series> {
+    let unit = Unit::parse(&kwargs.unit).map_err(core_err)?;
+    let mode = WidthMode::parse(&kwargs.width_mode).map_err(core_err)?;
+    let width = integers(&inputs[1], "width")?;
+    let side = strings(&inputs[2], "side")?;
+    let pad = chars(&inputs[3], "pad")?;
+    apply(&inputs[0], |array| {
+        kernels::str_pad(array, &width, &side, &pad, unit, mode)
+    })
+}
+
+#[polars_expr(output_type=String)]
+fn str_sub(inputs: &[Series], kwargs: UnitKwargs) -> PolarsResult<Series> {
+    let unit = Unit::parse(&kwargs.unit).map_err(core_err)?;
+    let start = integers(&inputs[1], "start")?;
+    let end = integers(&inputs[2], "end")?;
+    apply(&inputs[0], |array| {
+        kernels::str_sub(array, &start, &end, unit)
+    })
+}
+
+#[polars_expr(output_type_func=list_string)]
+fn str_match(inputs: &[Series], kwargs: OnePatternKwargs) -> PolarsResult<Series> {
+    let pattern = Matcher::new(&kwargs.pattern, (&kwargs.options).into()).map_err(core_err)?;
+    apply(&inputs[0], |array| kernels::str_match(array, &pattern))
+}
+
+#[polars_expr(output_type_func=list_int32)]
+fn str_locate(inputs: &[Series], kwargs: OnePatternKwargs) -> PolarsResult<Series> {
+    let pattern = Matcher::new(&kwargs.pattern, (&kwargs.options).into()).map_err(core_err)?;
+    apply(&inputs[0], |array| kernels::str_locate(array, &pattern))
+}
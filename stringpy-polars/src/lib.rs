@@ -0,0 +1,17 @@
+//! Polars expression plugin running the kernels of `stringpy_core`. Polars loads this library
+//! and calls the `str_*` expressions in [`expressions`] directly, without the GIL, the Python
+//! module only exists so that it can be built and installed as a Python package.
+mod convert;
+mod expressions;
+
+use pyo3::prelude::*;
+use pyo3_polars::PolarsAllocator;
+
+// series are shared with the polars of the Python process, memory has to come from its allocator
+#[global_allocator]
+static ALLOC: PolarsAllocator = PolarsAllocator::new();
+
+#[pymodule]
+fn _stringpy_polars(_m: &Bound<'_, PyModule>) -> PyResult<()> {
+    Ok(())
+}
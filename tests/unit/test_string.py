@@ -1,3 +1,6 @@
+import inspect
+import subprocess
+import sys
 import stringpy as sp
 import pyarrow as pa
 import pytest
@@ -24,6 +27,92 @@ def test_str_count():
     assert actual == expect
 
 
+def test_per_row_pattern():
+    array = ['a1b2', 'c3d4', None]
+    actual = sp.str_replace_all(array, pattern=[r'\d', r'[a-z]', 'x'], replace=['_', '', '']).to_pylist()
+    assert actual == ['a_b_', '34', None]
+    actual = sp.str_extract(array, pattern=[r'[a-z]', r'\d', 'x']).to_pylist()
+    assert actual == ['a', '3', None]
+    actual = sp.str_starts(array, pattern=['a', 'd', 'x']).to_pylist()
+    assert actual == [True, False, None]
+    with pytest.raises(ValueError):
+        sp.str_detect(array, pattern=['a', 'b'])
+
+
+def test_fixed_pattern():
+    array = ['a.b.c', 'abc', None]
+    assert sp.str_detect(array, pattern='.', fixed=True).to_pylist() == [True, False, None]
+    assert sp.str_count(array, pattern=sp.fixed('.')).to_pylist() == [2, 0, None]
+    actual = sp.str_replace_all(array, pattern=sp.fixed('.'), replace='$1').to_pylist()
+    assert actual == ['a$1b$1c', 'abc', None]
+    actual = sp.str_split(array, pattern='.', fixed=True).to_pylist()
+    assert actual == [['a', 'b', 'c'], ['abc'], [None]]
+    assert sp.str_locate(array, pattern='b.', fixed=True).to_pylist() == [[2, 4], [None, None], [None, None]]
+    assert sp.str_subset(array, pattern=sp.fixed('a.')).to_pylist() == ['a.b.c']
+    actual = sp.str_detect(['a', 'a', None], pattern=[sp.fixed('.'), '.', '.']).to_pylist()
+    assert actual == [False, True, None]
+
+
+def test_regex_flags():
+    array = ['Hello\nworld', 'HELLO', 'a1', None]
+    actual = sp.str_detect(array, pattern=sp.regex('hello', ignore_case=True)).to_pylist()
+    assert actual == [True, True, False, None]
+    actual = sp.str_detect(array, pattern=sp.regex('^world$', multiline=True)).to_pylist()
+    assert actual == [True, False, False, None]
+    assert sp.str_detect(array, pattern='^world$').to_pylist() == [False, False, False, None]
+    actual = sp.str_detect(array, pattern=sp.regex('o.w', dotall=True)).to_pylist()
+    assert actual == [True, False, False, None]
+    assert sp.str_detect(array, pattern='o.w').to_pylist() == [False, False, False, None]
+    pattern = sp.regex(r'''
+        [a-z]  # a letter
+        \d     # then a digit
+    ''', comments=True)
+    assert sp.str_detect(array, pattern=pattern).to_pylist() == [False, False, True, None]
+
+
+def test_pattern():
+    import pickle
+
+    pattern = sp.regex('(?P<x>a)(b)', ignore_case=True, dotall=True)
+    assert pattern.source == '(?P<x>a)(b)'
+    assert pattern.captures_len == 3
+    assert pattern.group_names == [None, 'x', None]
+    assert repr(pattern) == "regex('(?P<x>a)(b)', ignore_case=True, dotall=True)"
+
+    loaded = pickle.loads(pickle.dumps(pattern))
+    assert isinstance(loaded, sp.Pattern)
+    assert loaded.source == pattern.source
+    assert (loaded.fixed, loaded.ignore_case, loaded.multiline, loaded.dotall,
+            loaded.comments, loaded.unicode) == (False, True, False, True, False, True)
+    assert loaded.group_names == [None, 'x', None]
+    actual = sp.str_extract(['xAB', 'ab', 'b', None], pattern=loaded, group=1).to_pylist()
+    assert actual == ['A', 'a', None, None]
+
+    pattern = sp.fixed('(a)', ignore_case=True)
+    assert pattern.captures_len == 1
+    assert pattern.group_names == [None]
+    loaded = pickle.loads(pickle.dumps(pattern))
+    assert (loaded.source, loaded.fixed, loaded.ignore_case) == ('(a)', True, True)
+    assert sp.str_detect(['(A)', 'a', None], pattern=loaded).to_pylist() == [True, False, None]
+
+
+def test_str_starts_ends_pattern():
+    array = ['a.bc', 'Abc', 'xa1', None]
+    assert sp.str_starts(array, pattern='a.').to_pylist() == [True, False, False, None]
+    assert sp.str_starts(array, pattern=sp.regex('a.')).to_pylist() == [True, False, False, None]
+    actual = sp.str_starts(array, pattern=sp.regex('a', ignore_case=True)).to_pylist()
+    assert actual == [True, True, False, None]
+    actual = sp.str_starts(array, pattern=sp.fixed('A.', ignore_case=True)).to_pylist()
+    assert actual == [True, False, False, None]
+    assert sp.str_ends(array, pattern=sp.regex(r'\d')).to_pylist() == [False, False, True, None]
+    actual = sp.str_ends(array, pattern=sp.regex(r'[a-z] \d  # letter and digit', comments=True)).to_pylist()
+    assert actual == [False, False, True, None]
+    actual = sp.str_ends(array, pattern=[sp.regex('C', ignore_case=True), 'c', '']).to_pylist()
+    assert actual == [True, True, True, None]
+    actual = sp.str_starts(array, pattern=sp.regex('a.'), negate=True).to_pylist()
+    assert actual == [False, True, True, None]
+
+
 def test_str_remove_ascent():
     actual = sp.str_remove_ascent(
         ['tôi thấy mệt mỏi', 'hôm nay trời nắng', None]).to_pylist()
@@ -31,6 +120,96 @@ def test_str_remove_ascent():
     assert actual == expect
 
 
+def test_case_locale():
+    array = ['istanbul', 'ISTANBUL', None]
+    assert sp.str_to_upper(array).to_pylist() == ['ISTANBUL', 'ISTANBUL', None]
+    assert sp.str_to_upper(array, locale='tr').to_pylist() == ['İSTANBUL', 'ISTANBUL', None]
+    assert sp.str_to_lower(array, locale='tr').to_pylist() == ['istanbul', 'ıstanbul', None]
+    assert sp.str_to_title(['ilk iş'], locale='tr').to_pylist() == ['İlk İş']
+    with pytest.raises(ValueError):
+        sp.str_to_upper(array, locale='not a locale')
+
+
+def test_str_to_title_words():
+    array = ['the LORD of the rings', 'a  b\tc\nd', '', 'o\'neil 3rd-party', None]
+    actual = sp.str_to_title(array).to_pylist()
+    expect = ['The LORD Of The Rings', 'A  B\tC\nD', '', 'O\'neil 3rd-Party', None]
+    assert actual == expect
+    actual = sp.str_to_title(array, lower_rest=True, keep_lower=['of', 'THE']).to_pylist()
+    expect = ['The Lord of the Rings', 'A  B\tC\nD', '', 'O\'neil 3rd-Party', None]
+    assert actual == expect
+
+
+def test_str_to_sentence_terminators():
+    array = ['hi! how are you?\tfine. «really»', 'first line\nsecond line', '', None]
+    actual = sp.str_to_sentence(array).to_pylist()
+    expect = ['Hi! How are you?\tFine. «Really»', 'First line\nSecond line', '', None]
+    assert actual == expect
+    actual = sp.str_to_sentence(['SHOUTING. STILL'], lower_rest=True).to_pylist()
+    assert actual == ['Shouting. Still']
+
+
+def test_str_to_sentence_abbreviations():
+    array = ['dr. smith', 'a.b. c', 'i need you here. right now!', 'see fig. 2. then stop']
+    actual = sp.str_to_sentence(array, locale='en').to_pylist()
+    expect = ['Dr. smith', 'A.b. c', 'I need you here. Right now!', 'See fig. 2. Then stop']
+    assert actual == expect
+    # the abbreviations are those of the locale
+    actual = sp.str_to_sentence(['dr. smith'], locale='de').to_pylist()
+    assert actual == ['Dr. Smith']
+    # single letters are not taken for initials
+    actual = sp.str_to_sentence(['vitamin c. then'], locale='en').to_pylist()
+    assert actual == ['Vitamin c. Then']
+
+
+def test_str_to_sentence_keeps_unicode_boundaries():
+    # abbreviations only prevent the splits before lowercase text, those of UAX #29 are all kept
+    actual = sp.str_to_sentence(['I said no. Then I left.'], lower_rest=True).to_pylist()
+    assert actual == ['I said no. Then i left.']
+    actual = sp.str_to_sentence(['DR. SMITH IS HERE. HE LEFT'], locale='en', lower_rest=True).to_pylist()
+    assert actual == ['Dr. Smith is here. He left']
+
+
+def test_str_casefold():
+    actual = sp.str_casefold(['Straße', 'STRASSE', 'ΣΊΣΥΦΟΣ', None]).to_pylist()
+    expect = ['strasse', 'strasse', 'σίσυφοσ', None]
+    assert actual == expect
+
+
+def test_str_equal():
+    array = ['STRASSE', 'Cafe\u0301', 'ISTANBUL', None]
+    actual = sp.str_equal(array, other=['straße', 'CAFÉ', 'istanbul', 'x'], ignore_case=True).to_pylist()
+    assert actual == [True, True, True, None]
+    actual = sp.str_equal(array, other=['straße', 'CAFÉ', 'istanbul', 'x'], ignore_case=True,
+                          locale='tr').to_pylist()
+    assert actual == [True, True, False, None]
+    assert sp.str_equal(array, other='STRASSE').to_pylist() == [True, False, False, None]
+    actual = sp.str_equal(['İstanbul', 'I\u0307stanbul', 'Istanbul'], other='istanbul', ignore_case=True,
+                          locale='tr').to_pylist()
+    assert actual == [True, True, False]
+
+
+def test_str_remove_ascent_keeps_script():
+    actual = sp.str_remove_ascent(['Đà Nẵng', 'Ёлка', 'が', 'e\u0301', None]).to_pylist()
+    expect = ['Da Nang', 'Елка', 'が', 'e', None]
+    assert actual == expect
+
+
+def test_str_transliterate():
+    actual = sp.str_transliterate(['Crème brûlée', 'Ёлка', None]).to_pylist()
+    expect = ['Creme brulee', 'Iolka', None]
+    assert actual == expect
+
+
+def test_str_normalize():
+    array = ['e\u0301', '\u00e9', 'ﬁ', None]
+    assert sp.str_normalize(array).to_pylist() == ['\u00e9', '\u00e9', 'ﬁ', None]
+    assert sp.str_normalize(array, form='NFD').to_pylist() == ['e\u0301', 'e\u0301', 'ﬁ', None]
+    assert sp.str_normalize(array, form='NFKC').to_pylist() == ['\u00e9', '\u00e9', 'fi', None]
+    with pytest.raises(ValueError):
+        sp.str_normalize(array, form='nfc')
+
+
 def test_str_replace():
     actual = sp.str_replace(
         pa.array(['aa', 'bb', 'cc']), pattern=r'a', replace='b').to_pylist()
@@ -66,6 +245,59 @@ def test_str_detect():
     assert actual == expect
 
 
+@pytest.mark.parametrize('fixed', [False, True])
+def test_str_detect_any(fixed):
+    array = ['abcd', 'xyz', None, 'a.c', '']
+    patterns = ['ab', 'bc', 'abc', '.']
+
+    actual = sp.str_detect_any(array, patterns=patterns, fixed=fixed).to_pylist()
+    # `.` matches any character as a regex, only a dot as a literal
+    expect = [True, True, None, True, False] if not fixed else [True, False, None, True, False]
+    assert actual == expect
+
+    actual = sp.str_detect_any(array, patterns=[], fixed=fixed).to_pylist()
+    expect = [False, False, None, False, False]
+    assert actual == expect
+
+
+@pytest.mark.parametrize('fixed', [False, True])
+def test_str_which_patterns(fixed):
+    array = ['abcd', 'xyz', None, 'a.c', '']
+    patterns = ['ab', 'bc', 'abc', '.']
+
+    # overlapping matches are all reported, in the order of the patterns
+    actual = sp.str_which_patterns(array, patterns=patterns, fixed=fixed).to_pylist()
+    if fixed:
+        expect = [[0, 1, 2], [], None, [3], []]
+    else:
+        expect = [[0, 1, 2, 3], [3], None, [3], []]
+    assert actual == expect
+
+    actual = sp.str_which_patterns(array, patterns=[], fixed=fixed).to_pylist()
+    expect = [[], [], None, [], []]
+    assert actual == expect
+
+
+@pytest.mark.parametrize('fixed', [False, True])
+def test_str_case_when(fixed):
+    array = ['abcd', 'xyz', None, 'a.c', '']
+    # the first case in the list wins, not the leftmost match in the string
+    cases = [('c', 'c'), ('ab', 'ab'), ('.', 'dot')]
+
+    actual = sp.str_case_when(array, cases=cases, default='other', fixed=fixed).to_pylist()
+    expect = ['c', 'dot', None, 'c', 'other'] if not fixed else ['c', 'other', None, 'c', 'other']
+    assert actual == expect
+
+    # strings matching no case are null without a default
+    actual = sp.str_case_when(array, cases=cases, fixed=fixed).to_pylist()
+    expect = ['c', 'dot', None, 'c', None] if not fixed else ['c', None, None, 'c', None]
+    assert actual == expect
+
+    actual = sp.str_case_when(array, cases=[], default='other', fixed=fixed).to_pylist()
+    expect = ['other', 'other', None, 'other', 'other']
+    assert actual == expect
+
+
 def test_str_trunc():
     actual = sp.str_trunc(['toi muon mot giac ngu',
                            'doi bung qua roi nhi',
@@ -127,6 +359,50 @@ def test_str_length():
     assert actual == expect
 
 
+def test_str_length_unit():
+    array = ['He\u0301llo', '\U0001F468\u200d\U0001F469\u200d\U0001F467', 'Hà', None]
+    assert sp.str_length(array).to_pylist() == [5, 1, 2, None]
+    assert sp.str_length(array, unit='codepoints').to_pylist() == [6, 5, 2, None]
+    assert sp.str_length(array, unit='bytes').to_pylist() == [7, 18, 3, None]
+    with pytest.raises(ValueError):
+        sp.str_length(array, unit='words')
+
+
+def test_str_length_conjuncts():
+    # consonants joined by a virama form a single character (GB9c)
+    array = ['क्षि', 'ম্যা', 'a\r\nb']
+    assert sp.str_length(array).to_pylist() == [1, 1, 3]
+    assert sp.str_sub(array, start=0, end=1).to_pylist() == ['क्षि', 'ম্যা', 'a']
+
+
+def test_str_sub_graphemes():
+    actual = sp.str_sub(['He\u0301llo', 'nội'], start=1, end=3).to_pylist()
+    expect = ['e\u0301l', 'ội']
+    assert actual == expect
+    actual = sp.str_trunc(['He\u0301llo'], width=2, ellipsis='').to_pylist()
+    assert actual == ['He\u0301']
+
+
+def test_str_width():
+    actual = sp.str_width(['abc', '日本語', 'He\u0301llo', 'ｈｉ', '', None]).to_pylist()
+    expect = [3, 6, 5, 4, 0, None]
+    assert actual == expect
+    # emoji sequences, a Hangul syllable of conjoining jamo and a lone vowel jamo
+    actual = sp.str_width(['👨\u200d👩\u200d👧', '👍🏽', '☺\ufe0f', '\u1100\u1161', '\u1160']).to_pylist()
+    assert actual == [2, 2, 2, 2, 0]
+
+
+def test_str_display_width_mode():
+    array = ['ab', '日本', '한']
+    actual = sp.str_pad(array, width=4, pad='.', width_mode='display').to_pylist()
+    assert actual == ['..ab', '日本', '..한']
+    actual = sp.str_trunc(['日本語です', 'abcdefgh'], width=4, ellipsis='…',
+                          width_mode='display').to_pylist()
+    assert actual == ['日本…', 'abcd…']
+    with pytest.raises(ValueError):
+        sp.str_pad(array, width=4, width_mode='columns')
+
+
 def test_str_pad():
     actual = sp.str_pad(['a', 'b', 'c', None], width=3,
                         side='left', pad='0').to_pylist()
@@ -168,3 +444,338 @@ def test_raises_group_out_of_index():
     with pytest.raises(ValueError) as exc_info:
         sp.str_trim(['abc'], side='wrong')
     # assert str(exc_info.value) == "side must be one of 'left', 'right', 'both'"
+
+
+def test_large_string():
+    array = pa.array(['aa', 'bb', None], type=pa.large_string())
+    actual = sp.str_replace_all(array, pattern=r'a', replace='b')
+    assert actual.type == pa.large_string()
+    assert actual.to_pylist() == ['bb', 'bb', None]
+
+    actual = sp.str_split(array, pattern=r'a')
+    assert actual.type.value_type == pa.large_string()
+
+    actual = sp.str_detect(array, pattern=r'a').to_pylist()
+    assert actual == [True, False, None]
+
+
+def test_dictionary():
+    array = pa.array(['aa', 'bb', None, 'aa']).dictionary_encode()
+    actual = sp.str_to_upper(array)
+    assert pa.types.is_dictionary(actual.type)
+    assert actual.dictionary.to_pylist() == ['AA', 'BB']
+    assert actual.to_pylist() == ['AA', 'BB', None, 'AA']
+
+    actual = sp.str_replace_all(array, pattern=r'a', replace='c').to_pylist()
+    assert actual == ['cc', 'bb', None, 'cc']
+
+    actual = sp.str_detect(array, pattern=r'a').to_pylist()
+    assert actual == [True, False, None, True]
+
+    assert sp.str_subset(array, pattern=r'a').to_pylist() == ['aa', 'aa']
+    actual = sp._stringpy.str_which(array, pattern=r'a', negate=False, fixed=False)
+    assert actual.to_pylist() == [0, 3]
+    assert sp.str_unique(array).to_pylist() == ['aa', 'bb', None]
+    assert sp.str_c(array, collapse='-') == 'aa-bb--aa'
+
+
+def test_chunked_array():
+    array = pa.chunked_array([['aa', 'bb'], [None, 'ab']])
+    actual = sp.str_replace_all(array, pattern=r'a', replace='c')
+    assert isinstance(actual, pa.ChunkedArray)
+    assert [len(c) for c in actual.chunks] == [2, 2]
+    assert actual.to_pylist() == ['cc', 'bb', None, 'cb']
+
+    actual = sp.str_pad(array, width=[3, 3, 3, 4], side='left', pad='0')
+    assert actual.to_pylist() == ['0aa', '0bb', None, '00ab']
+
+    actual = sp.str_which(array, pattern=r'a')
+    assert actual.to_pylist() == [0, 3]
+
+    table = pa.table({'x': array})
+    actual = sp.str_detect(table.column('x'), pattern=r'a')
+    assert actual.to_pylist() == [True, False, None, True]
+
+    assert sp.str_c(array, collapse='-') == 'aa-bb--ab'
+
+    # the length of per-row arguments is checked once against the whole array, not per chunk
+    from stringpy import _stringpy
+    with pytest.raises(ValueError, match='Length of `pattern`'):
+        _stringpy.str_detect(array, pattern=['a', 'b', 'a', 'b', 'a'], fixed=False)
+
+
+class CapsuleArray:
+    """Arrow producer exposing only the PyCapsule interface"""
+
+    def __init__(self, array):
+        self.array = array
+
+    def __arrow_c_array__(self, requested_schema=None):
+        return self.array.__arrow_c_array__(requested_schema)
+
+
+class CapsuleStream:
+    """Arrow producer exposing only the PyCapsule stream interface"""
+
+    def __init__(self, array):
+        self.array = array
+
+    def __arrow_c_stream__(self, requested_schema=None):
+        return self.array.__arrow_c_stream__(requested_schema)
+
+
+def test_pycapsule_interface():
+    array = CapsuleArray(pa.array(['aa', 'bb', None]))
+    actual = sp.str_to_upper(array).to_pylist()
+    assert actual == ['AA', 'BB', None]
+    actual = sp.str_detect(array, pattern='a').to_pylist()
+    assert actual == [True, False, None]
+    actual = sp.str_detect(array, pattern=['a', 'b', 'c']).to_pylist()
+    assert actual == [True, True, None]
+    with pytest.raises(ValueError):
+        sp.str_detect(array, pattern=['a', 'b'])
+
+    stream = CapsuleStream(pa.chunked_array([['aa'], ['bb', None]]))
+    actual = sp.str_to_upper(stream)
+    assert isinstance(actual, pa.ChunkedArray)
+    assert actual.to_pylist() == ['AA', 'BB', None]
+
+
+class WrongCapsules:
+    """Arrow producer returning its schema and array capsules swapped"""
+
+    def __init__(self, array):
+        self.array = array
+
+    def __arrow_c_array__(self, requested_schema=None):
+        schema, array = self.array.__arrow_c_array__(requested_schema)
+        return array, schema
+
+
+def test_pycapsule_names():
+    with pytest.raises(ValueError, match="named 'arrow_schema', got 'arrow_array'"):
+        sp.str_to_upper(WrongCapsules(pa.array(['aa'])))
+
+
+def test_without_pyarrow(monkeypatch):
+    array = CapsuleArray(pa.array(['aa', 'bb', None]))
+    # importing a module set to None in sys.modules raises an ImportError
+    monkeypatch.setitem(sys.modules, 'pyarrow', None)
+    result = sp.str_to_upper(array)
+    assert isinstance(result, sp._stringpy.ArrowArray)
+    assert len(result) == 3
+    with pytest.raises(ImportError, match='pyarrow is required'):
+        sp.str_to_upper(['aa'])
+    monkeypatch.undo()
+    assert pa.array(result).to_pylist() == ['AA', 'BB', None]
+
+    # stringpy itself imports without pyarrow
+    code = "import sys; sys.modules['pyarrow'] = None; import stringpy"
+    subprocess.run([sys.executable, '-c', code], check=True)
+
+
+def test_str_stream():
+    table = pa.table({'x': ['aa', 'bb', None, 'ab'], 'y': [1, 2, 3, 4]})
+    reader = pa.RecordBatchReader.from_batches(
+        table.schema, table.to_batches(max_chunksize=2))
+
+    batches = list(sp.str_stream(reader, 'x', sp.str_detect, pattern=r'a'))
+    assert len(batches) == 2
+    assert batches[0].to_pydict() == {'x': [True, False], 'y': [1, 2]}
+    assert batches[1].to_pydict() == {'x': [None, True], 'y': [3, 4]}
+
+    stream = sp.str_stream(table, 'x', sp.str_to_upper)
+    actual = pa.RecordBatchReader.from_stream(stream).read_all()
+    assert actual.column('x').to_pylist() == ['AA', 'BB', None, 'AB']
+
+    with pytest.raises(ValueError):
+        sp.str_stream(table, 'z', sp.str_to_upper)
+
+
+def test_num_threads():
+    sp.set_num_threads(3)
+    assert sp.get_num_threads() == 3
+
+    array = pa.array(['ab', None, 'cd'] * 10000)
+    actual = sp.str_replace(array, pattern=r'b', replace='x')
+    assert actual.to_pylist() == ['ax', None, 'cd'] * 10000
+    actual = sp.str_sub(array, start=[1] * len(array), end=2)
+    assert actual.to_pylist() == ['b', None, 'd'] * 10000
+
+    sp.set_num_threads()
+    assert sp.get_num_threads() >= 1
+
+
+def test_many_rows():
+    values = [None if i % 7 == 0 else 'x' * (i % 5) for i in range(5000)]
+    for type in [pa.string(), pa.large_string()]:
+        array = pa.array(values, type=type)
+        actual = sp.str_dup(array, times=3)
+        assert actual.type == type
+        assert actual.to_pylist() == [None if x is None else x * 3 for x in values]
+
+
+def test_unchanged_rows_are_not_copied():
+    array = pa.array(['aa', None, 'bb'])
+    actual = sp.str_trim(array)
+    assert actual.to_pylist() == ['aa', None, 'bb']
+    assert actual.buffers()[2].address == array.buffers()[2].address
+
+    actual = sp.str_trim(pa.array(['aa ', None, 'bb'])).to_pylist()
+    assert actual == ['aa', None, 'bb']
+
+    # a wide pad that does not fit in the columns left adds nothing
+    array = pa.array(['你好', None, 'abc'])
+    actual = sp.str_pad(array, width=3, pad='你', width_mode='display')
+    assert actual.buffers()[2].address == array.buffers()[2].address
+
+    # case mappings leaving every row as is
+    array = pa.array(['ÉTÉ 1', None, 'ABC'])
+    actual = sp.str_to_upper(array)
+    assert actual.buffers()[2].address == array.buffers()[2].address
+    lower = sp.str_to_lower(array)
+    actual = sp.str_to_lower(lower)
+    assert actual.to_pylist() == ['été 1', None, 'abc']
+    assert actual.buffers()[2].address == lower.buffers()[2].address
+
+
+def test_polars_namespace():
+    # polars >= 1.0 stores strings as views, the plugin has to convert them itself
+    import polars as pl
+    import stringpy.polars  # noqa: F401
+
+    assert int(pl.__version__.split('.')[0]) >= 1
+    df = pl.DataFrame({'x': ['aa ', None, 'ab'], 'p': ['a', 'b', 'b'], 'n': [2, 1, 0]})
+    assert df.schema['x'] == pl.String
+    actual = df.lazy().select(
+        pl.col('x').stringpy.trim(),
+        pl.col('x').stringpy.detect(pattern='b').alias('detect'),
+        pl.col('x').stringpy.detect(pattern=pl.col('p')).alias('per_row'),
+        pl.col('x').stringpy.count(pattern=sp.fixed('A', ignore_case=True)).alias('count'),
+        pl.col('x').stringpy.starts(pattern='a').alias('starts'),
+        pl.col('x').stringpy.dup(times=pl.col('n')).alias('dup'),
+        pl.col('x').stringpy.split(pattern='a').alias('split'),
+        pl.col('x').stringpy.locate(pattern='b').alias('locate'),
+        pl.col('x').stringpy.case_when(cases=[('b', 'has b')], default='no b').alias('case_when'),
+    ).collect()
+    assert actual['x'].to_list() == ['aa', None, 'ab']
+    assert actual['detect'].to_list() == [False, None, True]
+    assert actual['per_row'].to_list() == [True, None, True]
+    assert actual['count'].to_list() == [2, None, 1]
+    assert actual['starts'].to_list() == [True, None, True]
+    assert actual['dup'].to_list() == ['aa aa ', None, '']
+    assert actual['split'].to_list() == [['', '', ' '], [None], ['', 'b']]
+    assert actual['locate'].to_list() == [[None, None], [None, None], [1, 2]]
+    assert actual['case_when'].to_list() == ['no b', None, 'has b']
+    assert actual.schema['split'] == pl.List(pl.String)
+    assert actual.schema['locate'] == pl.List(pl.Int32)
+
+    # functions changing the number of rows, on more than one batch
+    df = pl.DataFrame({'x': ['b', 'a', None, 'b'] * 1000})
+    actual = df.lazy().select(pl.col('x').stringpy.unique()).collect()
+    assert actual['x'].to_list() == ['b', 'a', None]
+    actual = df.lazy().select(pl.col('x').stringpy.subset(pattern='a')).collect()
+    assert actual['x'].to_list() == ['a'] * 1000
+    actual = df.lazy().select(pl.col('x').stringpy.to_upper()).collect()
+    assert actual['x'].to_list() == ['B', 'A', None, 'B'] * 1000
+
+    with pytest.raises(TypeError, match='polars expression'):
+        pl.col('x').stringpy.detect(pattern=['a', 'b'])
+    with pytest.raises(pl.exceptions.PolarsError, match='must be a single character'):
+        pl.DataFrame({'x': ['a']}).select(pl.col('x').stringpy.pad(width=3, pad='ab'))
+
+
+def test_pandas_accessor():
+    pd = pytest.importorskip('pandas')
+    import stringpy.pandas  # noqa: F401
+
+    s = pd.Series(['aa ', None, 'ab'], index=[3, 1, 2], name='x')
+    actual = s.stringpy.trim()
+    assert actual.tolist() == ['aa', None, 'ab']
+    assert actual.index.tolist() == [3, 1, 2]
+    assert actual.name == 'x'
+
+    s = s.astype(pd.ArrowDtype(pa.string()))
+    actual = s.stringpy.detect(pattern='b')
+    assert actual.dtype == pd.ArrowDtype(pa.bool_())
+    assert actual.index.tolist() == [3, 1, 2]
+    assert actual.tolist() == [False, None, True]
+
+
+def test_register_udfs():
+    import pyarrow.compute as pc
+    from stringpy.compute import register_udfs
+    register_udfs()
+    register_udfs()
+
+    array = pa.array(['aa', None, 'ba'])
+    actual = pc.call_function('stringpy_replace_all', [array, pa.scalar('a'), pa.scalar('c')])
+    assert actual.to_pylist() == ['cc', None, 'bc']
+
+    table = pa.table({'x': array})
+    expr = pc.Expression._call('stringpy_detect', [pc.field('x'), pc.scalar('b')])
+    assert table.filter(expr).column('x').to_pylist() == ['ba']
+    # options such as `fixed` keep their default and don't change the arity
+    assert pc.get_function('stringpy_detect').arity == 2
+    assert pc.get_function('stringpy_replace_all').arity == 3
+
+    # large_string arrays have their own functions, keeping the 64-bit offsets
+    array = pa.array(['aa', None, 'ba'], type=pa.large_string())
+    actual = pc.call_function('stringpy_replace_all_large', [array, pa.scalar('a'), pa.scalar('c')])
+    assert actual.type == pa.large_string()
+    assert actual.to_pylist() == ['cc', None, 'bc']
+    actual = pc.call_function('stringpy_split_large', [array, pa.scalar('a')])
+    assert actual.type == pa.large_list(pa.large_string())
+    actual = pc.call_function('stringpy_detect_large', [array, pa.scalar('b')])
+    assert actual.to_pylist() == [False, None, True]
+
+
+def test_udfs_cover_every_function():
+    import pyarrow.compute as pc
+    from stringpy.compute import NOT_UDF, OPTIONS, LARGE_SUFFIX, register_udfs
+    from stringpy.utils import ELEMENTWISE, NOT_ELEMENTWISE
+    register_udfs()
+
+    # every function is either elementwise or not, only aggregations and streams are neither
+    exported = {name for name in dir(sp) if name.startswith('str_')}
+    assert exported == set(ELEMENTWISE) | set(NOT_ELEMENTWISE) | {'str_c', 'str_combine', 'str_stream'}
+
+    for name in ELEMENTWISE:
+        if name in NOT_UDF:
+            continue
+        params = inspect.signature(getattr(sp, name)).parameters
+        arity = len([p for p in params if p not in OPTIONS])
+        for suffix in ['', LARGE_SUFFIX]:
+            assert pc.get_function('stringpy_' + name[len('str_'):] + suffix).arity == arity, name
+
+
+def test_pattern_cache():
+    array = ['ab', 'cd']
+    sp.clear_cache()
+    sp.set_cache_size(2)
+    try:
+        sp.str_detect(array, pattern='a')
+        sp.str_detect(array, pattern='a')
+        info = sp.cache_info()
+        assert (info['hits'], info['misses'], info['currsize'], info['maxsize']) == (1, 1, 1, 2)
+
+        # using `a` makes `b` the least recently used pattern, evicted by `c`
+        sp.str_detect(array, pattern='b')
+        sp.str_detect(array, pattern='a')
+        sp.str_detect(array, pattern='c')
+        sp.str_detect(array, pattern='a')
+        info = sp.cache_info()
+        assert (info['hits'], info['misses'], info['currsize']) == (3, 3, 2)
+        sp.str_detect(array, pattern='b')
+        assert sp.cache_info()['misses'] == 4
+
+        # size 0 disables the cache
+        sp.set_cache_size(0)
+        assert sp.cache_info()['currsize'] == 0
+        sp.str_detect(array, pattern='a')
+        sp.str_detect(array, pattern='a')
+        info = sp.cache_info()
+        assert (info['hits'], info['misses'], info['currsize']) == (3, 6, 0)
+    finally:
+        sp.set_cache_size()
+        sp.clear_cache()